@@ -1,8 +1,6 @@
 use markov_str::*;
 use rand::SeedableRng;
 use regex::Regex;
-#[cfg(feature = "serialize")]
-use serde_json;
 use std::fs::{self, read_to_string};
 
 fn main() {
@@ -78,7 +76,7 @@ fn main() {
 		for _ in 0..10 {
 			println!(
 				"Deserialized: {}",
-				m.generate_start("among the       ", 25, &mut rng).unwrap()
+				m.generate_start(&matches, 25, &mut rng).unwrap()
 			);
 		}
 	}
@@ -0,0 +1,431 @@
+use std::{
+	error::Error,
+	fmt,
+	hash::BuildHasher,
+	io::{self, BufRead, Write},
+};
+
+use hashbrown::hash_map::RawEntryMut;
+use lasso::{Key, Spur};
+use smallvec::SmallVec;
+
+use crate::{
+	export::token_label, ChainItem, RawMarkovChain, MAX_STATE_SIZE, SENTENCE_END,
+	SENTENCE_START,
+};
+
+/// Log probability that is written for events that never happen.
+const LOG_ZERO: f64 = -99.0;
+
+/// Highest multiplier that is tried while converting probabilities into integer weights.
+const MAX_MULTIPLIER: usize = 1000;
+
+/// Highest weight that is given while importing. Probabilities that can't be converted into integer weights up to
+/// this are scaled down to it.
+const MAX_IMPORT_WEIGHT: f64 = 1000.0;
+
+/// Tokens, key and successors of a state.
+type ArpaState<'a> = (Vec<&'a str>, &'a [Spur], &'a ChainItem);
+
+/// Error that is returned when an ARPA file can't be read.
+#[derive(Debug)]
+pub enum ArpaError {
+	/// Reading from the reader failed.
+	Io(io::Error),
+	/// The file is not a valid ARPA file. `line` starts from 1.
+	Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ArpaError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ArpaError::Io(e) => write!(f, "can't read the ARPA file: {}", e),
+			ArpaError::Parse { line, message } => {
+				write!(f, "invalid ARPA file at line {}: {}", line, message)
+			}
+		}
+	}
+}
+
+impl Error for ArpaError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			ArpaError::Io(e) => Some(e),
+			ArpaError::Parse { .. } => None,
+		}
+	}
+}
+
+impl From<io::Error> for ArpaError {
+	#[inline]
+	fn from(e: io::Error) -> Self {
+		ArpaError::Io(e)
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Writes the chain as an [ARPA](https://cmusphinx.github.io/wiki/arpaformat/) back-off n-gram language model.
+	///
+	/// A chain with the state size of `n` is written as an `n + 1`-gram model. Probabilities are the
+	/// maximum likelihood estimates of the counts, written with enough digits to be read back exactly.
+	/// The chain only backs off to a shorter state when the longer one is never seen. Tokens that
+	/// never follow a state have the probability of 0 after it, so the back-off weight of every
+	/// state is `-99`, which stands for `log10(0)`.
+	///
+	/// Sentence markers are written as `<s>` and `</s>`. N-grams are ordered by their tokens, so
+	/// equal chains give equal files.
+	pub fn write_arpa<W: Write>(&self, mut w: W) -> io::Result<()> {
+		let total = self
+			.token_counts
+			.iter()
+			.fold(0usize, |a, &c| a.saturating_add(c)) as f64;
+		let mut unigrams: Vec<_> = self
+			.cache
+			.iter()
+			.map(|(spur, s)| (spur, token_label(s)))
+			.collect();
+		unigrams.sort_unstable_by_key(|&(_, s)| s);
+
+		let mut orders: Vec<Vec<ArpaState<'_>>> = vec![Vec::new(); self.state_size];
+		for (k, v) in &self.items {
+			if let Some(order) = orders.get_mut(k.len().wrapping_sub(1)) {
+				order.push((self.resolve_all(k), k, v));
+			}
+		}
+		for order in &mut orders {
+			order.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+		}
+
+		writeln!(w, "\\data\\")?;
+		writeln!(w, "ngram 1={}", unigrams.len())?;
+		for (i, order) in orders.iter().enumerate() {
+			let count: usize = order.iter().map(|(_, _, v)| v.counts().len()).sum();
+			writeln!(w, "ngram {}={}", i + 2, count)?;
+		}
+
+		writeln!(w)?;
+		writeln!(w, "\\1-grams:")?;
+		for (spur, s) in unigrams {
+			let count = self
+				.token_counts
+				.get(spur.into_usize())
+				.copied()
+				.unwrap_or(0);
+			let logp = log10_ratio(count, total);
+
+			if self.state_size > 0 && self.items.contains_key([spur].as_slice()) {
+				writeln!(w, "{}\t{}\t{}", logp, s, LOG_ZERO)?;
+			} else {
+				writeln!(w, "{}\t{}", logp, s)?;
+			}
+		}
+
+		for (i, order) in orders.iter().enumerate() {
+			writeln!(w)?;
+			writeln!(w, "\\{}-grams:", i + 2)?;
+
+			for (context, key, v) in order {
+				let total = v.items.len() as f64;
+				let mut successors: Vec<_> = v
+					.counts()
+					.into_iter()
+					.map(|(s, c)| (token_label(self.cache.resolve(&s)), s, c))
+					.collect();
+				successors.sort_unstable_by_key(|&(s, _, _)| s);
+
+				for (s, spur, count) in successors {
+					let logp = log10_ratio(count, total);
+					let ngram = context.join(" ");
+
+					// n-grams that are also states can be backed off from
+					let is_state = i + 1 < self.state_size && {
+						let mut key: SmallVec<[Spur; N]> =
+							SmallVec::from_slice(key);
+						key.push(spur);
+						self.items.contains_key(key.as_slice())
+					};
+
+					if is_state {
+						writeln!(
+							w,
+							"{}\t{} {}\t{}",
+							logp, ngram, s, LOG_ZERO
+						)?;
+					} else {
+						writeln!(w, "{}\t{} {}", logp, ngram, s)?;
+					}
+				}
+			}
+		}
+
+		writeln!(w)?;
+		writeln!(w, "\\end\\")
+	}
+}
+
+impl<const N: usize, H: BuildHasher + Default> RawMarkovChain<N, H> {
+	/// Reads a chain from an [ARPA](https://cmusphinx.github.io/wiki/arpaformat/) back-off n-gram language model.
+	///
+	/// An `n`-gram model is read as a chain with the state size of `n - 1`. Back-off weights are ignored, as the
+	/// chain backs off without any penalty.
+	///
+	/// The chain stores integer counts, so the probabilities of every state are converted into the smallest
+	/// counts that keep their ratios. Files that are written by [`RawMarkovChain::write_arpa()`] are read back
+	/// with their original counts, unless the counts of a state have a common divisor or are greater than 1000.
+	/// Models whose probabilities can't be converted this way, like smoothed ones, are approximated.
+	///
+	/// `<s>` and `</s>` are read as the sentence markers, and
+	/// [`ChainConfig::sentence_boundaries`](crate::ChainConfig) is set if any of them is found.
+	pub fn from_arpa<R: BufRead>(reader: R) -> Result<RawMarkovChain<N, H>, ArpaError> {
+		let mut lines = reader.lines().enumerate();
+		let mut next_line = || -> Result<Option<(usize, String)>, ArpaError> {
+			match lines.next() {
+				Some((i, l)) => Ok(Some((i + 1, l?))),
+				None => Ok(None),
+			}
+		};
+
+		// header
+		let mut counts = Vec::new();
+		let mut in_data = false;
+		let mut section = loop {
+			let Some((lnum, line)) = next_line()? else {
+				return Err(parse_error(0, "unexpected end of file"));
+			};
+			let line = line.trim();
+
+			if line == "\\data\\" {
+				in_data = true;
+			} else if let Some(rest) = line.strip_prefix("ngram ").filter(|_| in_data) {
+				let (n, count) = rest
+					.split_once('=')
+					.and_then(|(n, c)| {
+						Some((
+							n.trim().parse::<usize>().ok()?,
+							c.trim().parse::<usize>().ok()?,
+						))
+					})
+					.ok_or_else(|| parse_error(lnum, "invalid n-gram count"))?;
+				if n != counts.len() + 1 {
+					return Err(parse_error(
+						lnum,
+						"n-gram counts are not in order",
+					));
+				}
+				counts.push(count);
+			} else if line.starts_with('\\') && in_data {
+				break (lnum, line.to_string());
+			}
+		};
+
+		if counts.is_empty() {
+			return Err(parse_error(section.0, "no n-gram counts"));
+		}
+		if counts.len() - 1 > MAX_STATE_SIZE {
+			return Err(parse_error(section.0, "too many n-gram orders"));
+		}
+
+		let mut chain = RawMarkovChain::with_hasher(counts.len() - 1, H::default());
+		let mut unigrams: Vec<(Spur, f64)> = Vec::new();
+
+		for (i, &count) in counts.iter().enumerate() {
+			let order = i + 1;
+			if section.1 != format!("\\{}-grams:", order) {
+				return Err(parse_error(
+					section.0,
+					format!("expected {}-grams", order),
+				));
+			}
+
+			// context, successor, probability
+			// the count is not trusted, so a corrupted one can't allocate too much up front
+			let mut ngrams: Vec<(SmallVec<[Spur; N]>, Spur, f64)> =
+				Vec::with_capacity(count.min(4096));
+			section = loop {
+				let Some((lnum, line)) = next_line()? else {
+					return Err(parse_error(0, "unexpected end of file"));
+				};
+				let line = line.trim();
+
+				if line.is_empty() {
+					continue;
+				}
+				if line.starts_with('\\') {
+					break (lnum, line.to_string());
+				}
+
+				let mut fields = line.split_whitespace();
+				let logp: f64 = fields
+					.next()
+					.and_then(|p| p.parse().ok())
+					.ok_or_else(|| parse_error(lnum, "invalid probability"))?;
+				let tokens: SmallVec<[Spur; N]> = fields
+					.by_ref()
+					.take(order)
+					.map(|t| chain.intern(import_token(t), 0))
+					.collect();
+				if tokens.len() != order || fields.count() > 1 {
+					return Err(parse_error(
+						lnum,
+						format!("expected {} tokens", order),
+					));
+				}
+
+				let p = if logp <= LOG_ZERO {
+					0.0
+				} else {
+					10f64.powf(logp)
+				};
+				let (succ, context) = tokens.split_last().unwrap();
+				ngrams.push((SmallVec::from_slice(context), *succ, p));
+			};
+
+			if ngrams.len() != count {
+				return Err(parse_error(
+					section.0,
+					format!(
+						"expected {} {}-grams, found {}",
+						count,
+						order,
+						ngrams.len()
+					),
+				));
+			}
+
+			if order == 1 {
+				unigrams = ngrams.into_iter().map(|(_, s, p)| (s, p)).collect();
+				continue;
+			}
+
+			ngrams.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+			for group in ngrams.chunk_by(|a, b| a.0 == b.0) {
+				let probs: Vec<f64> = group.iter().map(|(_, _, p)| *p).collect();
+				let weights = import_weights(&probs);
+
+				for ((context, succ, _), weight) in group.iter().zip(weights) {
+					if weight == 0 {
+						continue;
+					}
+
+					match chain
+						.items
+						.raw_entry_mut()
+						.from_key(context.as_slice())
+					{
+						RawEntryMut::Occupied(mut view) => {
+							view.get_mut().add_weighted(*succ, weight);
+						}
+						RawEntryMut::Vacant(view) => {
+							view.insert(
+								context.clone(),
+								ChainItem::new_weighted(
+									*succ, weight,
+								),
+							);
+						}
+					}
+				}
+			}
+		}
+
+		if section.1 != "\\end\\" {
+			return Err(parse_error(section.0, "expected \\end\\"));
+		}
+
+		chain.config.sentence_boundaries = [SENTENCE_START, SENTENCE_END]
+			.iter()
+			.any(|m| chain.cache.get(m).is_some());
+
+		let probs: Vec<f64> = unigrams.iter().map(|(_, p)| *p).collect();
+		let weights = import_weights(&probs);
+		for ((s, _), weight) in unigrams.into_iter().zip(weights) {
+			chain.token_counts[s.into_usize()] = weight;
+		}
+		chain.measure_memory();
+
+		Ok(chain)
+	}
+}
+
+/// Returns the token of the label in an ARPA file, which maps `<s>` and `</s>` back to the sentence
+/// markers.
+#[inline]
+fn import_token(t: &str) -> &str {
+	match t {
+		"<s>" => SENTENCE_START,
+		"</s>" => SENTENCE_END,
+		t => t,
+	}
+}
+
+/// Returns `log10(count / total)`, or [`LOG_ZERO`] if `count` is 0.
+#[inline]
+fn log10_ratio(count: usize, total: f64) -> f64 {
+	if count == 0 {
+		LOG_ZERO
+	} else {
+		(count as f64 / total).log10()
+	}
+}
+
+/// Converts the probabilities into the smallest integer weights with the same ratios.
+///
+/// Probabilities that don't have such ratios, like those of smoothed models, or whose weights would be greater than
+/// [`MAX_IMPORT_WEIGHT`], are scaled so that the greatest weight is [`MAX_IMPORT_WEIGHT`].
+fn import_weights(probs: &[f64]) -> Vec<usize> {
+	let min = probs
+		.iter()
+		.copied()
+		.filter(|&p| p > 0.0)
+		.fold(f64::INFINITY, f64::min);
+	let max = probs.iter().copied().fold(0.0, f64::max);
+	if max == 0.0 {
+		return vec![0; probs.len()];
+	}
+
+	for m in 1..=MAX_MULTIPLIER {
+		let m = m as f64;
+		// greater multipliers only give greater weights
+		if max / min * m > MAX_IMPORT_WEIGHT {
+			break;
+		}
+
+		let is_int = probs.iter().all(|p| {
+			let w = p / min * m;
+			(w - w.round()).abs() <= 1e-6 * w.max(1.0)
+		});
+		if is_int {
+			if let Some(weights) =
+				probs.iter().map(|p| to_weight(p / min * m)).collect()
+			{
+				return weights;
+			}
+		}
+	}
+
+	let scale = MAX_IMPORT_WEIGHT / max;
+	probs.iter()
+		.map(|&p| {
+			if p > 0.0 {
+				to_weight(p * scale).unwrap_or(0).max(1)
+			} else {
+				0
+			}
+		})
+		.collect()
+}
+
+/// Rounds the weight into an integer, or returns `None` if it is not between 0 and [`MAX_IMPORT_WEIGHT`].
+#[inline]
+fn to_weight(w: f64) -> Option<usize> {
+	let w = w.round();
+	(0.0..=MAX_IMPORT_WEIGHT).contains(&w).then_some(w as usize)
+}
+
+#[inline]
+fn parse_error(line: usize, message: impl Into<String>) -> ArpaError {
+	ArpaError::Parse {
+		line,
+		message: message.into(),
+	}
+}
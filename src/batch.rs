@@ -0,0 +1,46 @@
+use std::hash::BuildHasher;
+
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::RawMarkovChain;
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Generates `count` texts of given length in parallel, using the global
+	/// [rayon](https://docs.rs/rayon/latest/rayon/) thread pool.
+	///
+	/// Every text is generated with its own [`StdRng`], which is derived from `seed` and the index
+	/// of the text. Results are in the order of their indices, so the output only depends on the
+	/// chain and `seed`, not on the number of threads.
+	///
+	/// Returns `None` if there is no state.
+	pub fn generate_batch(&self, count: usize, length: usize, seed: u64) -> Option<Vec<String>>
+	where
+		H: Sync,
+	{
+		if self.is_empty() {
+			return None;
+		}
+
+		let res = (0..count)
+			.into_par_iter()
+			.map(|i| {
+				let mut rng = batch_rng(seed, i as u64);
+				self.generate(length, &mut rng).unwrap_or_default()
+			})
+			.collect();
+
+		Some(res)
+	}
+}
+
+/// Returns the RNG of the text at `index`. Seeds of the RNGs are different for every index, so
+/// their streams are independent.
+#[inline]
+fn batch_rng(seed: u64, index: u64) -> StdRng {
+	let mut bytes = [0; 32];
+	bytes[..8].copy_from_slice(&seed.to_le_bytes());
+	bytes[8..16].copy_from_slice(&index.to_le_bytes());
+
+	StdRng::from_seed(bytes)
+}
@@ -0,0 +1,135 @@
+use std::hash::BuildHasher;
+
+use lasso::{Capacity, Key, Rodeo, Spur};
+
+use crate::{interner::Interner, RawMarkovChain};
+
+#[cfg(feature = "serialize")]
+use {
+	foldhash::fast::FixedState,
+	serde::{ser::SerializeStruct, Serialize, Serializer},
+};
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Renumbers the interned tokens and tags by their alphabetical order and sorts the successors
+	/// of every state.
+	///
+	/// Chains that are trained with the same data are equal after this, even if the data is
+	/// added in different orders. Generation from the chain stays deterministic, but may not give
+	/// the same text as before for the same RNG.
+	///
+	/// Tokens that the chain doesn't use are dropped. Keys of a shared interner can't be
+	/// renumbered, so a chain with one gets its own interner, which has the tokens of the chain.
+	pub fn canonicalize(&mut self) {
+		let used = self.referenced_tokens();
+		let mut tokens: Vec<(&str, Spur)> = self
+			.cache
+			.iter()
+			.filter(|(k, _)| used[k.into_usize()])
+			.map(|(k, s)| (s, k))
+			.collect();
+		tokens.sort_unstable();
+
+		let mut cache = Rodeo::with_capacity(Capacity::for_strings(tokens.len()));
+		let mut token_counts = vec![0; tokens.len()];
+		let mut surfaces = vec![
+			Vec::new();
+			if self.surfaces.is_empty() {
+				0
+			} else {
+				tokens.len()
+			}
+		];
+		// old key to new key
+		let mut keys = vec![Spur::default(); used.len()];
+		for (s, old) in tokens {
+			let new = cache.get_or_intern(s);
+			keys[old.into_usize()] = new;
+			token_counts[new.into_usize()] = self
+				.token_counts
+				.get(old.into_usize())
+				.copied()
+				.unwrap_or(0);
+			if let Some(forms) = self.surfaces.get_mut(old.into_usize()) {
+				forms.sort_unstable();
+				surfaces[new.into_usize()] = std::mem::take(forms);
+			}
+		}
+
+		let mut tags: Vec<(&str, Spur)> =
+			self.tag_cache.iter().map(|(k, s)| (s, k)).collect();
+		tags.sort_unstable();
+
+		let mut tag_cache = Rodeo::with_capacity(Capacity::for_strings(tags.len()));
+		let mut tag_keys = vec![Spur::default(); tags.len()];
+		for (s, old) in tags {
+			tag_keys[old.into_usize()] = tag_cache.get_or_intern(s);
+		}
+
+		let mut items = Vec::with_capacity(self.items.len());
+		for (mut k, mut v) in self.items.drain() {
+			k.iter_mut().for_each(|s| *s = keys[s.into_usize()]);
+			v.items.iter_mut().for_each(|s| *s = keys[s.into_usize()]);
+
+			if v.tags.is_empty() {
+				v.items.sort_unstable();
+			} else {
+				let mut pairs: Vec<_> = v
+					.items
+					.iter()
+					.zip(&v.tags)
+					.map(|(&s, t)| (s, t.map(|t| tag_keys[t.into_usize()])))
+					.collect();
+				pairs.sort_unstable();
+				(v.items, v.tags) = pairs.into_iter().unzip();
+			}
+
+			items.push((k, v));
+		}
+
+		// the table is empty after draining, and keeps its hasher
+		self.items.extend(items);
+		self.cache = Interner::Owned(cache);
+		self.token_counts = token_counts;
+		self.surfaces = surfaces;
+		self.tag_cache = tag_cache;
+		self.measure_memory();
+	}
+
+	/// Returns a wrapper that serializes the chain in its canonical form, see
+	/// [`RawMarkovChain::canonicalize()`]. States are written in sorted order, so chains that are
+	/// trained with the same data are serialized into identical bytes.
+	///
+	/// Output can be deserialized as a regular [`RawMarkovChain`]. Serializing clones the chain.
+	#[cfg(feature = "serialize")]
+	#[inline]
+	pub fn canonical(&self) -> Canonical<'_, N, H> {
+		Canonical(self)
+	}
+}
+
+/// Serializes a [`RawMarkovChain`] in its canonical form. Created by
+/// [`RawMarkovChain::canonical()`].
+#[cfg(feature = "serialize")]
+pub struct Canonical<'a, const N: usize, H = FixedState>(&'a RawMarkovChain<N, H>);
+
+#[cfg(feature = "serialize")]
+impl<const N: usize, H: BuildHasher + Clone> Serialize for Canonical<'_, N, H> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut chain = self.0.clone();
+		chain.canonicalize();
+
+		let mut items: Vec<_> = chain.items.iter().collect();
+		items.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+		let mut state = serializer.serialize_struct("RawMarkovChain", 7)?;
+		state.serialize_field("items", &items)?;
+		state.serialize_field("state_size", &chain.state_size)?;
+		state.serialize_field("cache", &chain.cache)?;
+		state.serialize_field("token_counts", &chain.token_counts)?;
+		state.serialize_field("config", &chain.config)?;
+		state.serialize_field("surfaces", &chain.surfaces)?;
+		state.serialize_field("tag_cache", &chain.tag_cache)?;
+		state.end()
+	}
+}
@@ -1,27 +1,49 @@
-use std::collections::VecDeque;
+use std::{borrow::Cow, cmp::Ordering, collections::VecDeque, hash::BuildHasher};
 
+use foldhash::fast::FixedState;
 use hashbrown::{hash_map::RawEntryMut, HashMap};
-use lasso::{Capacity, Rodeo, Spur};
-use rand::{seq::SliceRandom, RngCore};
+use lasso::{Capacity, Key, Rodeo, Spur};
+use rand::{seq::SliceRandom, Rng, RngCore};
 use smallvec::SmallVec;
 
-#[cfg(feature = "serialize")]
-use {
-	serde::{Deserialize, Serialize},
-	serde_json_any_key::*,
+use crate::{
+	interner::Interner,
+	limits::{state_bytes, successor_bytes, token_bytes},
+	normalize::SurfaceForms,
+	ChainConfig, FallbackPolicy, MarkovError, SeedMatch, SENTENCE_END, SENTENCE_START,
 };
 
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
 /// Represents a Markov Chain that is designed to generate text.
 ///
 /// States with sizes that are lesser than or equal to `N` are stored inline, thus are more performant.
 /// Those of sizes that are greater are stored in a seperate [`Vec`].
-#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+///
+/// States are hashed with `H`, which is a fixed and publicly known hasher by default. Chains that
+/// are trained with untrusted input can use a randomly seeded hasher instead, like
+/// [`std::hash::RandomState`], so that the input can't be crafted to collide. Serialized chains
+/// don't depend on the hasher.
+#[cfg_attr(
+	feature = "serialize",
+	derive(Deserialize),
+	serde(
+		try_from = "crate::validate::UnvalidatedChain<N, H>",
+		bound(deserialize = "H: BuildHasher + Default")
+	)
+)]
 #[derive(Clone)]
-pub struct RawMarkovChain<const N: usize> {
-	#[cfg_attr(feature = "serialize", serde(with = "any_key_map"))]
-	items: HashMap<SmallVec<[Spur; N]>, ChainItem, foldhash::fast::FixedState>,
-	state_size: usize,
-	cache: Rodeo,
+pub struct RawMarkovChain<const N: usize, H = FixedState> {
+	pub(crate) items: HashMap<SmallVec<[Spur; N]>, ChainItem, H>,
+	pub(crate) state_size: usize,
+	pub(crate) cache: Interner,
+	pub(crate) token_counts: Vec<usize>,
+	pub(crate) config: ChainConfig,
+	pub(crate) surfaces: Vec<SurfaceForms>,
+	pub(crate) tag_cache: Rodeo,
+	// running estimate of the memory usage, for checking Limits::max_memory cheaply
+	pub(crate) memory: usize,
 }
 
 /// Represents a Markov Chain that is designed to generate text.
@@ -36,11 +58,7 @@ impl<const N: usize> RawMarkovChain<N> {
 	/// It will not allocate until the first insertion.
 	#[inline]
 	pub fn new(state_size: usize) -> RawMarkovChain<N> {
-		RawMarkovChain {
-			items: HashMap::with_hasher(foldhash::fast::FixedState::default()),
-			state_size,
-			cache: Rodeo::new(),
-		}
+		RawMarkovChain::with_hasher(state_size, FixedState::default())
 	}
 
 	/// Creates an empty MarkovChain with the specified capacity.
@@ -49,50 +67,80 @@ impl<const N: usize> RawMarkovChain<N> {
 	/// reallocating. If `capacity` is 0, the hashmap will not allocate.
 	#[inline]
 	pub fn with_capacity(state_size: usize, capacity: usize) -> RawMarkovChain<N> {
-		RawMarkovChain {
-			items: HashMap::with_capacity_and_hasher(
-				capacity,
-				foldhash::fast::FixedState::default(),
-			),
+		RawMarkovChain::with_capacity_and_hasher(
 			state_size,
-			cache: Rodeo::with_capacity(Capacity::for_strings(capacity)),
-		}
+			capacity,
+			FixedState::default(),
+		)
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Creates an empty MarkovChain, which hashes its states with the given hasher.
+	///
+	/// The hashmap and the cache of the MarkovChain is initially created with the capacity of 0.
+	/// It will not allocate until the first insertion.
+	#[inline]
+	pub fn with_hasher(state_size: usize, hasher: H) -> RawMarkovChain<N, H> {
+		let mut chain = RawMarkovChain {
+			items: HashMap::with_hasher(hasher),
+			state_size,
+			cache: Interner::default(),
+			token_counts: Vec::new(),
+			config: ChainConfig::default(),
+			surfaces: Vec::new(),
+			tag_cache: Rodeo::new(),
+			memory: 0,
+		};
+		chain.measure_memory();
+
+		chain
+	}
+
+	/// Creates an empty MarkovChain with the specified capacity, which hashes its states with the
+	/// given hasher.
+	///
+	/// The hashmap and the cache of the MarkovChain will be able to hold at least `capacity` elements without
+	/// reallocating. If `capacity` is 0, the hashmap will not allocate.
+	#[inline]
+	pub fn with_capacity_and_hasher(
+		state_size: usize,
+		capacity: usize,
+		hasher: H,
+	) -> RawMarkovChain<N, H> {
+		let mut chain = RawMarkovChain {
+			items: HashMap::with_capacity_and_hasher(capacity, hasher),
+			state_size,
+			cache: Interner::Owned(Rodeo::with_capacity(Capacity::for_strings(
+				capacity,
+			))),
+			token_counts: Vec::with_capacity(capacity),
+			config: ChainConfig::default(),
+			surfaces: Vec::new(),
+			tag_cache: Rodeo::new(),
+			memory: 0,
+		};
+		chain.measure_memory();
+
+		chain
+	}
+
+	/// Returns the hasher that the states are hashed with.
+	#[inline]
+	pub fn hasher(&self) -> &H {
+		self.items.hasher()
 	}
 
 	/// Adds text as training data. The tokens will be created with the regex of the MarkovChain.
+	///
+	/// Tokens are not added if they exceed the [`Limits`](crate::Limits) of the chain, see
+	/// [`RawMarkovChain::try_add_tokens()`].
+	#[inline]
 	pub fn add_tokens<T>(&mut self, tokens: T)
 	where
 		T: IntoIterator<Item: AsRef<str>>,
 	{
-		let intern_tokens = tokens.into_iter().map(|t| self.cache.get_or_intern(t));
-		let tokens: Vec<_> = intern_tokens.collect();
-
-		if tokens.is_empty() {
-			return;
-		}
-
-		for win in tokens.windows(tokens.len().min(self.state_size + 1)) {
-			let wlen = win.len();
-			let rel = win.last().unwrap();
-
-			// if wlen is less than 2, there is nothing to do
-			for i in 2..=wlen {
-				// win[(wlen - 1)] == rel == win.last()
-				// this is equal to win.iter().rev().skip(1).take(i - 1).rev()
-				let slice = &win[(wlen - i)..(wlen - 1)];
-				match self.items.raw_entry_mut().from_key(slice) {
-					RawEntryMut::Occupied(mut view) => {
-						view.get_mut().add(*rel);
-					}
-					RawEntryMut::Vacant(view) => {
-						view.insert(
-							SmallVec::from_slice(slice),
-							ChainItem::new(*rel),
-						);
-					}
-				}
-			}
-		}
+		let _ = self.train(tokens, 1, None);
 	}
 
 	/// Adds text as training data with a weight. The tokens will be created with the regex of the MarkovChain.
@@ -100,6 +148,10 @@ impl<const N: usize> RawMarkovChain<N> {
 	/// It is mostly equivalent to calling [`MarkovChain::add_text()`] `weight` number of times, but
 	/// may not yield the same results when [`MarkovChain::generate()`] is called with same RNG,
 	/// due to internal workings.
+	///
+	/// Tokens are not added if they exceed the [`Limits`](crate::Limits) of the chain, see
+	/// [`RawMarkovChain::try_add_tokens_weighted()`].
+	#[inline]
 	pub fn add_tokens_weighted<T>(&mut self, tokens: T, weight: usize)
 	where
 		T: IntoIterator<Item: AsRef<str>>,
@@ -108,35 +160,130 @@ impl<const N: usize> RawMarkovChain<N> {
 			return;
 		}
 
-		let intern_tokens = tokens.into_iter().map(|t| self.cache.get_or_intern(t));
-		let tokens: Vec<_> = intern_tokens.collect();
+		let _ = self.train(tokens, weight, None);
+	}
+
+	/// Adds the tokens as training data, every transition is added `weight` number of times with
+	/// the given tag.
+	///
+	/// Returns [`MarkovError::LimitExceeded`] without changing the chain if the tokens exceed the
+	/// limits of the chain.
+	pub(crate) fn train<T>(
+		&mut self,
+		tokens: T,
+		weight: usize,
+		tag: Option<&str>,
+	) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		let boundaries = self.config.sentence_boundaries;
+		let tokens: Vec<_> = tokens
+			.into_iter()
+			.filter(|t| {
+				!boundaries || ![SENTENCE_START, SENTENCE_END].contains(&t.as_ref())
+			})
+			.collect();
 
 		if tokens.is_empty() {
-			return;
+			return Ok(());
+		}
+		if !self.config.limits.is_unlimited() {
+			self.check_limits(&tokens, weight, tag.is_some())?;
 		}
 
-		for win in tokens.windows(tokens.len().min(self.state_size + 1)) {
-			let wlen = win.len();
-			let rel = win.last().unwrap();
-
-			// if wlen is less than 2, there is nothing to do
-			for i in 2..=wlen {
-				// win[(wlen - 1)] == rel == win.last()
-				// this is equal to win.iter().rev().skip(1).take(i - 1).rev()
-				let slice = &win[(wlen - i)..(wlen - 1)];
-				match self.items.raw_entry_mut().from_key(slice) {
-					RawEntryMut::Occupied(mut view) => {
-						view.get_mut().add_weighted(*rel, weight);
-					}
+		let tag = tag.map(|t| self.tag_cache.get_or_intern(t));
+		let tokens: Vec<_> = tokens
+			.iter()
+			.map(|t| self.intern(t.as_ref(), weight))
+			.collect();
+		self.add_transitions(tokens, weight, tag);
+
+		Ok(())
+	}
+
+	/// Adds every transition of the interned tokens `weight` number of times with the given tag,
+	/// with the sentence markers if [`ChainConfig::sentence_boundaries`] is set.
+	pub(crate) fn add_transitions(
+		&mut self,
+		mut tokens: Vec<Spur>,
+		weight: usize,
+		tag: Option<Spur>,
+	) {
+		// every state of the sentence start is made of start markers, so generation can start from
+		// any of its sizes
+		if self.config.sentence_boundaries {
+			let start = self.intern(SENTENCE_START, 0);
+			let end = self.intern(SENTENCE_END, 0);
+			tokens.splice(0..0, std::iter::repeat_n(start, self.state_size));
+			tokens.push(end);
+		}
+
+		let items = &mut self.items;
+		let mut added: usize = 0;
+		for_each_transition(
+			&tokens,
+			self.state_size,
+			self.config.min_order,
+			|slice, rel| {
+				let item = match items.raw_entry_mut().from_key(slice) {
+					RawEntryMut::Occupied(view) => view.into_mut(),
 					RawEntryMut::Vacant(view) => {
+						added = added.saturating_add(state_bytes::<N>(
+							slice.len(),
+						));
 						view.insert(
 							SmallVec::from_slice(slice),
-							ChainItem::new_weighted(*rel, weight),
-						);
+							ChainItem::default(),
+						)
+						.1
 					}
-				}
-			}
+				};
+				item.add_tagged(*rel, weight, tag);
+				added =
+					added.saturating_add(weight.saturating_mul(
+						successor_bytes(!item.tags.is_empty()),
+					));
+			},
+		);
+		self.memory = self.memory.saturating_add(added);
+	}
+
+	/// Interns the folded token and adds `weight` to its count.
+	#[inline]
+	pub(crate) fn intern(&mut self, token: &str, weight: usize) -> Spur {
+		let spur = self.intern_folded(token);
+		self.add_surface(spur, token, weight);
+		self.add_count(spur, weight);
+
+		spur
+	}
+
+	/// Interns the folded form of the token and returns its key.
+	#[inline]
+	pub(crate) fn intern_folded(&mut self, token: &str) -> Spur {
+		let len = self.cache.len();
+		let folded = self.config.normalization.apply(token);
+		let bytes = token_bytes(folded.len());
+		let spur = match folded {
+			Cow::Borrowed(t) => self.cache.get_or_intern(t),
+			Cow::Owned(t) => self.cache.get_or_intern(t),
+		};
+		if self.cache.len() != len {
+			self.memory = self.memory.saturating_add(bytes);
 		}
+
+		spur
+	}
+
+	/// Adds `weight` to the count of the interned token, saturating at `usize::MAX`.
+	#[inline]
+	pub(crate) fn add_count(&mut self, spur: Spur, weight: usize) {
+		let idx = spur.into_usize();
+		if idx >= self.token_counts.len() {
+			self.token_counts.resize(idx + 1, 0);
+		}
+		self.token_counts[idx] = self.token_counts[idx].saturating_add(weight);
 	}
 
 	/// Generates text of given length.
@@ -148,14 +295,7 @@ impl<const N: usize> RawMarkovChain<N> {
 			return None;
 		}
 
-		let mut res = String::new();
-		for next in self.iter(length, rng) {
-			res.push_str(next);
-			res.push(' ');
-		}
-		res.pop();
-
-		Some(res)
+		Some(join_tokens(self.iter(length, rng)))
 	}
 
 	/// Generates text of given length, with accordance to the given starting value.
@@ -174,14 +314,7 @@ impl<const N: usize> RawMarkovChain<N> {
 			return None;
 		}
 
-		let mut res = String::new();
-		for next in self.iter_start(start, length, rng) {
-			res.push_str(next);
-			res.push(' ');
-		}
-		res.pop();
-
-		Some(res)
+		Some(join_tokens(self.iter_start(start, length, rng)))
 	}
 
 	/// Returns the number of states the chain has.
@@ -191,9 +324,14 @@ impl<const N: usize> RawMarkovChain<N> {
 	}
 
 	/// Returns the number of string that are interned in cache.
+	///
+	/// Only the tokens that the chain uses are counted of a shared interner.
 	#[inline]
 	pub fn cache_len(&self) -> usize {
-		self.cache.len()
+		match self.cache {
+			Interner::Owned(_) => self.cache.len(),
+			Interner::Shared(_) => self.used_tokens().count(),
+		}
 	}
 
 	/// Returns whether the chain is empty or not.
@@ -209,28 +347,50 @@ impl<const N: usize> RawMarkovChain<N> {
 	}
 
 	/// Does the same thing as [`MarkovChain::generate()`] but instead of returning a String, returns a lazily evaluated iterator.
+	///
+	/// `rng` can be either borrowed or owned. Iterators that own their RNGs can be sent to other
+	/// threads and stored in structs.
 	#[inline]
-	pub fn iter<'a>(
-		&'a self,
-		count: usize,
-		rng: &'a mut dyn RngCore,
-	) -> MarkovChainIter<'a, N> {
+	pub fn iter<R: RngCore>(&self, count: usize, rng: R) -> MarkovChainIter<'_, N, R, H> {
 		MarkovChainIter {
 			chain: self,
-			count,
-			rng,
-			prev: Vec::with_capacity(self.state_size),
+			steps: Steps::new(count, rng, self.start_context(None::<&str>)),
 		}
 	}
 
 	/// Does the same thing as [`MarkovChain::generate_start()`] but instead of returning a String, returns a lazily evaluated iterator.
+	///
+	/// `rng` can be either borrowed or owned. Iterators that own their RNGs can be sent to other
+	/// threads and stored in structs.
 	#[inline]
-	pub fn iter_start<'a, T>(
-		&'a self,
+	pub fn iter_start<T, R>(
+		&self,
 		start: T,
 		count: usize,
-		rng: &'a mut dyn RngCore,
-	) -> MarkovChainIter<'a, N>
+		rng: R,
+	) -> MarkovChainIter<'_, N, R, H>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		MarkovChainIter {
+			chain: self,
+			steps: Steps::new(count, rng, self.start_context(start)),
+		}
+	}
+
+	/// Returns the context for starting the generation from the given tokens.
+	#[inline]
+	pub(crate) fn start_context<T>(&self, start: T) -> Context<N>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		self.seed_context(start).0
+	}
+
+	/// Returns the context for starting the generation from the given tokens, and which of the
+	/// tokens are used in it.
+	pub(crate) fn seed_context<T>(&self, start: T) -> (Context<N>, SeedMatch)
 	where
 		T: IntoIterator<Item: AsRef<str>>,
 	{
@@ -244,108 +404,303 @@ impl<const N: usize> RawMarkovChain<N> {
 			}
 		}
 
-		let prev = buf.into_iter().filter_map(|t| self.cache.get(t)).collect();
+		let mut prev = Context::new(self.state_size);
+		let mut seed = SeedMatch::default();
+		for t in buf {
+			match self.cache.get(self.config.normalization.apply(t.as_ref())) {
+				Some(s) => {
+					prev.push(s);
+					seed.matched += 1;
+				}
+				None => seed.unknown.push(t.as_ref().to_string()),
+			}
+		}
 
-		MarkovChainIter {
-			chain: self,
-			count,
-			rng,
-			prev,
+		if self.config.sentence_boundaries && prev.as_slice().is_empty() {
+			if let Some(start) = self.cache.get(SENTENCE_START) {
+				(0..self.state_size).for_each(|_| prev.push(start));
+			}
 		}
+
+		(prev, seed)
 	}
 
 	/// Returns the appropriate next step for the given previous state.
 	///
-	/// Returns `None` if there is no state.
-	fn next_step(&self, prev: &[Spur], rng: &mut impl RngCore) -> Option<Spur> {
+	/// Returns `None` if there is no state, if the sentence ends, or if the previous state is not
+	/// known and [`FallbackPolicy::Stop`] is used.
+	#[inline]
+	pub(crate) fn next_step(&self, prev: &[Spur], rng: &mut impl RngCore) -> Option<Spur> {
+		let (item, _) = self.step_source(prev, rng)?;
+		item.get_rand(rng).filter(|&s| !self.is_sentence_end(s))
+	}
+
+	/// Returns the state that the next step is picked from, with the length of the longest end of
+	/// the previous state that matches it. The length is 0 if none of the previous state is known,
+	/// and the state is picked randomly.
+	///
+	/// Returns `None` if there is no state, or if the previous state is not known and
+	/// [`FallbackPolicy::Stop`] is used.
+	pub(crate) fn step_source(
+		&self,
+		prev: &[Spur],
+		rng: &mut impl RngCore,
+	) -> Option<(&ChainItem, usize)> {
 		for i in 0..prev.len() {
 			let pslice = &prev[i..];
 
 			if let Some(res) = self.items.get(pslice) {
-				return res.get_rand(rng);
+				return Some((res, pslice.len()));
 			} else {
 				continue;
 			}
 		}
 
-		self.items
-			.values()
-			.collect::<Vec<&ChainItem>>()
-			.choose(rng)?
-			.get_rand(rng)
+		if !prev.is_empty() && self.config.fallback == FallbackPolicy::Stop {
+			return None;
+		}
+
+		self.fallback_state(rng).map(|item| (item, 0))
+	}
+
+	/// Returns `true` if the token is the end of a sentence.
+	#[inline]
+	pub(crate) fn is_sentence_end(&self, s: Spur) -> bool {
+		self.config.sentence_boundaries && self.cache.resolve(&s) == SENTENCE_END
+	}
+
+	/// Returns the next step from a random state, for when none of the previous state is known.
+	///
+	/// States are picked by the order of their tokens instead of the order of the hash table, so
+	/// the result does not depend on the capacity or the insertion history of the table, nor on
+	/// the keys of a shared interner.
+	///
+	/// Returns `None` if there is no state.
+	#[inline]
+	pub(crate) fn fallback(&self, rng: &mut impl RngCore) -> Option<Spur> {
+		self.fallback_state(rng)?.get_rand(rng)
+	}
+
+	/// Returns a random state, which is picked like [`RawMarkovChain::fallback()`].
+	///
+	/// Returns `None` if there is no state.
+	pub(crate) fn fallback_state(&self, rng: &mut impl RngCore) -> Option<&ChainItem> {
+		if self.items.is_empty() {
+			return None;
+		}
+
+		let mut states: Vec<_> = self.items.iter().collect();
+		let idx = rng.gen_range(0..states.len());
+		let (_, (_, item), _) =
+			states.select_nth_unstable_by(idx, |a, b| self.cmp_states(a.0, b.0));
+
+		Some(item)
+	}
+
+	/// Compares two states by their tokens.
+	#[inline]
+	pub(crate) fn cmp_states(&self, a: &[Spur], b: &[Spur]) -> Ordering {
+		let resolve = |s: &Spur| self.cache.resolve(s);
+		a.iter().map(resolve).cmp(b.iter().map(resolve))
+	}
+}
+
+/// Calls `f` with every state of the tokens and the token that follows it. States are from
+/// `min_order` up to `state_size` tokens long.
+pub(crate) fn for_each_transition<T>(
+	tokens: &[T],
+	state_size: usize,
+	min_order: usize,
+	mut f: impl FnMut(&[T], &T),
+) {
+	for win in tokens.windows(tokens.len().min(state_size + 1)) {
+		let wlen = win.len();
+		let rel = win.last().unwrap();
+
+		// if wlen is less than 2, there is nothing to do
+		for i in (min_order + 1).max(2)..=wlen {
+			// win[(wlen - 1)] == rel == win.last()
+			// this is equal to win.iter().rev().skip(1).take(i - 1).rev()
+			let slice = &win[(wlen - i)..(wlen - 1)];
+			f(slice, rel);
+		}
 	}
 }
 
 /// Iterator that iterates over generation steps.
-pub struct MarkovChainIter<'a, const N: usize> {
-	chain: &'a RawMarkovChain<N>,
-	count: usize,
-	rng: &'a mut dyn RngCore,
-	prev: Vec<Spur>,
+///
+/// Generic over the RNG, which can be either borrowed, like `&mut ThreadRng`, or owned.
+pub struct MarkovChainIter<'a, const N: usize, R, H = FixedState> {
+	pub(crate) chain: &'a RawMarkovChain<N, H>,
+	pub(crate) steps: Steps<N, R>,
 }
 
-impl<'a, const N: usize> Iterator for MarkovChainIter<'a, N> {
+impl<'a, const N: usize, R: RngCore, H: BuildHasher> Iterator for MarkovChainIter<'a, N, R, H> {
 	type Item = &'a str;
 
+	#[inline]
 	fn next(&mut self) -> Option<Self::Item> {
+		let chain = self.chain;
+		self.steps.step(|prev, rng| {
+			let next = chain.next_step(prev, rng)?;
+			Some((next, chain.resolve_surface(next)))
+		})
+	}
+}
+
+/// Progress of a generation, which every iterator over generation steps takes its steps with.
+pub(crate) struct Steps<const N: usize, R> {
+	pub(crate) count: usize,
+	rng: R,
+	prev: Context<N>,
+}
+
+impl<const N: usize, R: RngCore> Steps<N, R> {
+	/// Creates the progress of a generation of `count` steps, which starts from `prev`.
+	#[inline]
+	pub(crate) fn new(count: usize, rng: R, prev: Context<N>) -> Steps<N, R> {
+		Steps { count, rng, prev }
+	}
+
+	/// Takes a step, unless every step is taken. `f` picks the next token for the previous
+	/// tokens, and returns it with the item that the step yields.
+	///
+	/// Returns `None` if every step is taken, or if `f` returns `None`.
+	#[inline]
+	pub(crate) fn step<T>(
+		&mut self,
+		f: impl FnOnce(&[Spur], &mut R) -> Option<(Spur, T)>,
+	) -> Option<T> {
 		if self.count == 0 {
 			return None;
 		}
 		self.count -= 1;
 
-		let next_spur = self.chain.next_step(&self.prev, &mut self.rng)?;
-		let next = self.chain.cache.resolve(&next_spur);
+		let (next, res) = f(self.prev.as_slice(), &mut self.rng)?;
+		self.prev.push(next);
 
-		if self.prev.len() == self.chain.state_size {
-			self.prev.remove(0);
-		}
-		self.prev.push(next_spur);
+		Some(res)
+	}
+}
 
-		Some(next)
+/// Joins the tokens with spaces.
+pub(crate) fn join_tokens<'a>(tokens: impl IntoIterator<Item = &'a str>) -> String {
+	let mut res = String::new();
+	for next in tokens {
+		res.push_str(next);
+		res.push(' ');
 	}
+	res.pop();
+
+	res
 }
 
-/// Wrapper for Vec<Spur> to make some operations easier.
-#[cfg_attr(
-	feature = "serialize",
-	derive(Serialize, Deserialize),
-	serde(transparent)
-)]
+/// Last tokens of a generation, up to the state size of the chain.
+///
+/// Fixed capacity ring buffer. Every token is written twice, `size` apart, so the last tokens are
+/// always contiguous and pushing never shifts the buffer. Tokens are stored in pairs, so the buffer
+/// is inline if the size is lesser than or equal to `N`.
 #[derive(Clone)]
-struct ChainItem {
-	items: Vec<Spur>,
+pub(crate) struct Context<const N: usize> {
+	buf: SmallVec<[[Spur; 2]; N]>,
+	size: usize,
+	start: usize,
+	len: usize,
 }
 
-impl ChainItem {
-	/// Creates a ChainItem, which will also contain `s`.
+impl<const N: usize> Context<N> {
+	/// Creates an empty context that holds up to `size` tokens.
+	#[inline]
+	pub(crate) fn new(size: usize) -> Context<N> {
+		Context {
+			buf: SmallVec::from_elem([Spur::default(); 2], size),
+			size,
+			start: 0,
+			len: 0,
+		}
+	}
+
+	/// Pushes the token, removing the oldest one if the context is full.
+	#[inline]
+	pub(crate) fn push(&mut self, s: Spur) {
+		if self.size == 0 {
+			return;
+		}
+
+		let buf = self.buf.as_flattened_mut();
+		if self.len < self.size {
+			buf[self.len] = s;
+			buf[self.len + self.size] = s;
+			self.len += 1;
+		} else {
+			buf[self.start] = s;
+			buf[self.start + self.size] = s;
+			self.start = (self.start + 1) % self.size;
+		}
+	}
+
+	/// Returns the tokens, from the oldest to the newest.
 	#[inline]
-	fn new(s: Spur) -> ChainItem {
-		ChainItem { items: vec![s] }
+	pub(crate) fn as_slice(&self) -> &[Spur] {
+		&self.buf.as_flattened()[self.start..(self.start + self.len)]
 	}
+}
+
+/// Wrapper for Vec<Spur> to make some operations easier.
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Default)]
+pub(crate) struct ChainItem {
+	pub(crate) items: Vec<Spur>,
+	/// Tags of the items, in the same order. Empty if none of the items is tagged.
+	#[cfg_attr(feature = "serialize", serde(default))]
+	pub(crate) tags: Vec<Option<Spur>>,
+}
 
+impl ChainItem {
 	/// Creates a ChainItem, which will also contain `s` `weight` number of times.
 	#[inline]
-	fn new_weighted(s: Spur, weight: usize) -> ChainItem {
+	pub(crate) fn new_weighted(s: Spur, weight: usize) -> ChainItem {
 		ChainItem {
 			items: vec![s; weight],
+			tags: Vec::new(),
 		}
 	}
 
-	/// Adds item.
+	/// Adds item `weight` number of times.
 	#[inline]
-	fn add(&mut self, s: Spur) {
-		self.items.push(s);
+	pub(crate) fn add_weighted(&mut self, s: Spur, weight: usize) {
+		self.add_tagged(s, weight, None);
 	}
 
-	/// Adds item `weight` number of times.
-	#[inline]
-	fn add_weighted(&mut self, s: Spur, weight: usize) {
-		self.items.extend(std::iter::repeat(s).take(weight));
+	/// Adds item `weight` number of times with the given tag.
+	pub(crate) fn add_tagged(&mut self, s: Spur, weight: usize, tag: Option<Spur>) {
+		// items that are added before the first tag are untagged
+		if tag.is_some() || !self.tags.is_empty() {
+			self.tags.resize(self.items.len(), None);
+			self.tags.extend(std::iter::repeat_n(tag, weight));
+		}
+		self.items.extend(std::iter::repeat_n(s, weight));
+	}
+
+	/// Returns every distinct item with the number of times it is added, ordered by the items.
+	pub(crate) fn counts(&self) -> Vec<(Spur, usize)> {
+		let mut sorted = self.items.clone();
+		sorted.sort_unstable();
+
+		let mut res: Vec<(Spur, usize)> = Vec::new();
+		for s in sorted {
+			match res.last_mut() {
+				Some((last, c)) if *last == s => *c += 1,
+				_ => res.push((s, 1)),
+			}
+		}
+
+		res
 	}
 
 	/// Gets a random item.
 	#[inline]
-	fn get_rand(&self, rng: &mut impl RngCore) -> Option<Spur> {
+	pub(crate) fn get_rand(&self, rng: &mut impl RngCore) -> Option<Spur> {
 		let res = *self
 			.items
 			// get a random item from the Vec
@@ -0,0 +1,329 @@
+use std::{hash::BuildHasher, sync::Arc};
+
+use foldhash::fast::FixedState;
+use lasso::Spur;
+
+use crate::{
+	error::check_state_size, interner::Interner, ChainItem, Limits, MarkovError, Normalization,
+	RawMarkovChain, ThreadedRodeo,
+};
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Reserved token that marks the start of a sentence, when
+/// [`ChainConfig::sentence_boundaries`] is set.
+pub const SENTENCE_START: &str = "\u{2}";
+
+/// Reserved token that marks the end of a sentence, when [`ChainConfig::sentence_boundaries`] is
+/// set.
+pub const SENTENCE_END: &str = "\u{3}";
+
+/// Greatest state size of a chain. Chains with greater state sizes are rejected while they are
+/// built or deserialized.
+pub const MAX_STATE_SIZE: usize = u16::MAX as usize;
+
+/// What to do when none of the previous tokens form a known state during generation.
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FallbackPolicy {
+	/// Continue from a random state.
+	#[default]
+	Random,
+	/// Stop the generation. The first step of a generation without any previous token still
+	/// starts from a random state.
+	Stop,
+}
+
+/// How [`RawMarkovChain::add_text()`] splits the text into tokens.
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Tokenizer {
+	/// Splits the text by whitespace.
+	#[default]
+	Whitespace,
+	/// Takes the words, like [`WORD_REGEX`](crate::WORD_REGEX). Words start with a letter or a
+	/// digit, may contain `'` and `-`, and may end with one of `.`, `!` and `?`. Everything else is
+	/// skipped.
+	Words,
+}
+
+impl Tokenizer {
+	/// Splits the text into tokens.
+	pub fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+		match self {
+			Tokenizer::Whitespace => text.split_whitespace().collect(),
+			Tokenizer::Words => {
+				let mut res = Vec::new();
+				let mut start = None;
+
+				for (i, c) in text.char_indices() {
+					match start {
+						None if c.is_alphanumeric() => start = Some(i),
+						Some(_) if c.is_alphanumeric()
+							|| c == '\'' || c == '-' => {}
+						Some(s) => {
+							let end = if matches!(c, '.' | '!' | '?') {
+								i + c.len_utf8()
+							} else {
+								i
+							};
+							res.push(&text[s..end]);
+							start = None;
+						}
+						None => {}
+					}
+				}
+				if let Some(s) = start {
+					res.push(&text[s..]);
+				}
+
+				res
+			}
+		}
+	}
+}
+
+/// Configuration of a [`RawMarkovChain`], which is stored and serialized with the chain. Created
+/// by [`ChainBuilder`].
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainConfig {
+	/// Size of the smallest states that are stored. States from `min_order` up to the state size
+	/// are stored, and generation backs off to the shorter ones down to it.
+	pub min_order: usize,
+	/// Whether the training data is split into sentences, which start with [`SENTENCE_START`] and
+	/// end with [`SENTENCE_END`]. Generation without a known seed starts at the start of a sentence
+	/// and stops at its end. Tokens that are equal to the markers are skipped while training.
+	pub sentence_boundaries: bool,
+	/// Rules for folding the tokens, see [`Normalization`].
+	pub normalization: Normalization,
+	/// What to do when none of the previous tokens form a known state.
+	pub fallback: FallbackPolicy,
+	/// Transitions that are seen fewer times than this are removed by
+	/// [`RawMarkovChain::prune()`].
+	pub min_count: usize,
+	/// How [`RawMarkovChain::add_text()`] splits the text into tokens.
+	pub tokenizer: Tokenizer,
+	/// Limits of the resources that training can use.
+	pub limits: Limits,
+}
+
+impl Default for ChainConfig {
+	#[inline]
+	fn default() -> Self {
+		ChainConfig {
+			min_order: 1,
+			sentence_boundaries: false,
+			normalization: Normalization::NONE,
+			fallback: FallbackPolicy::Random,
+			min_count: 1,
+			tokenizer: Tokenizer::Whitespace,
+			limits: Limits::default(),
+		}
+	}
+}
+
+/// Builder of a [`RawMarkovChain`] with a validated [`ChainConfig`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainBuilder {
+	state_size: usize,
+	capacity: usize,
+	config: ChainConfig,
+	interner: Option<Arc<ThreadedRodeo>>,
+}
+
+impl ChainBuilder {
+	/// Creates a builder for a chain with the given state size and the default configuration.
+	#[inline]
+	pub fn new(state_size: usize) -> ChainBuilder {
+		ChainBuilder {
+			state_size,
+			capacity: 0,
+			config: ChainConfig::default(),
+			interner: None,
+		}
+	}
+
+	/// Sets the initial capacity of the chain, see [`RawMarkovChain::with_capacity()`].
+	#[inline]
+	pub fn capacity(mut self, capacity: usize) -> ChainBuilder {
+		self.capacity = capacity;
+		self
+	}
+
+	/// Sets [`ChainConfig::min_order`].
+	#[inline]
+	pub fn min_order(mut self, min_order: usize) -> ChainBuilder {
+		self.config.min_order = min_order;
+		self
+	}
+
+	/// Sets [`ChainConfig::sentence_boundaries`].
+	#[inline]
+	pub fn sentence_boundaries(mut self, sentence_boundaries: bool) -> ChainBuilder {
+		self.config.sentence_boundaries = sentence_boundaries;
+		self
+	}
+
+	/// Sets [`ChainConfig::normalization`].
+	#[inline]
+	pub fn normalization(mut self, normalization: Normalization) -> ChainBuilder {
+		self.config.normalization = normalization;
+		self
+	}
+
+	/// Sets [`ChainConfig::fallback`].
+	#[inline]
+	pub fn fallback(mut self, fallback: FallbackPolicy) -> ChainBuilder {
+		self.config.fallback = fallback;
+		self
+	}
+
+	/// Sets [`ChainConfig::min_count`].
+	#[inline]
+	pub fn min_count(mut self, min_count: usize) -> ChainBuilder {
+		self.config.min_count = min_count;
+		self
+	}
+
+	/// Sets [`ChainConfig::tokenizer`].
+	#[inline]
+	pub fn tokenizer(mut self, tokenizer: Tokenizer) -> ChainBuilder {
+		self.config.tokenizer = tokenizer;
+		self
+	}
+
+	/// Sets the shared interner of the chain, see [`RawMarkovChain::with_interner()`].
+	#[inline]
+	pub fn interner(mut self, interner: Arc<ThreadedRodeo>) -> ChainBuilder {
+		self.interner = Some(interner);
+		self
+	}
+
+	/// Sets [`ChainConfig::limits`].
+	#[inline]
+	pub fn limits(mut self, limits: Limits) -> ChainBuilder {
+		self.config.limits = limits;
+		self
+	}
+
+	/// Creates the chain.
+	///
+	/// Returns [`MarkovError::InvalidConfig`] if the state size is 0 or greater than
+	/// [`MAX_STATE_SIZE`], or if the minimum order is not between 1 and the state size.
+	#[inline]
+	pub fn build<const N: usize>(self) -> Result<RawMarkovChain<N>, MarkovError> {
+		self.build_with_hasher(FixedState::default())
+	}
+
+	/// Creates the chain, which hashes its states with the given hasher. See [`RawMarkovChain`]
+	/// for why the hasher may be changed.
+	///
+	/// Returns [`MarkovError::InvalidConfig`] if the state size is 0, or if the minimum order is
+	/// not between 1 and the state size.
+	pub fn build_with_hasher<const N: usize, H: BuildHasher>(
+		self,
+		hasher: H,
+	) -> Result<RawMarkovChain<N, H>, MarkovError> {
+		check_state_size(self.state_size)?;
+		if self.config.min_order == 0 || self.config.min_order > self.state_size {
+			return Err(MarkovError::InvalidConfig(format!(
+				"minimum order must be between 1 and the state size, which is {}",
+				self.state_size
+			)));
+		}
+
+		let mut chain = RawMarkovChain::with_capacity_and_hasher(
+			self.state_size,
+			self.capacity,
+			hasher,
+		);
+		chain.config = self.config;
+		if let Some(interner) = self.interner {
+			chain.cache = Interner::Shared(interner);
+			chain.measure_memory();
+		}
+
+		Ok(chain)
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns a builder for a chain with the given state size.
+	#[inline]
+	pub fn builder(state_size: usize) -> ChainBuilder {
+		ChainBuilder::new(state_size)
+	}
+
+	/// Returns the configuration of the chain.
+	#[inline]
+	pub fn config(&self) -> &ChainConfig {
+		&self.config
+	}
+
+	/// Splits the text into tokens with [`ChainConfig::tokenizer`] and adds them as training data.
+	///
+	/// If [`ChainConfig::sentence_boundaries`] is set, every token that ends with `.`, `!` or `?`
+	/// ends a sentence.
+	///
+	/// Sentences are not added if they exceed the [`Limits`] of the chain, see
+	/// [`RawMarkovChain::try_add_text()`].
+	pub fn add_text(&mut self, text: &str) {
+		let tokens = self.config.tokenizer.tokenize(text);
+
+		if self.config.sentence_boundaries {
+			for sentence in tokens.split_inclusive(|t| t.ends_with(['.', '!', '?'])) {
+				self.add_tokens(sentence);
+			}
+		} else {
+			self.add_tokens(tokens);
+		}
+	}
+
+	/// Removes the transitions that are seen fewer than [`ChainConfig::min_count`] times, and the
+	/// states that are left without any transition.
+	///
+	/// The counts of the tokens are kept.
+	pub fn prune(&mut self) {
+		let min_count = self.config.min_count;
+		if min_count <= 1 {
+			return;
+		}
+
+		self.items.retain(|_, item| {
+			let rare: Vec<_> = item
+				.counts()
+				.into_iter()
+				.filter(|&(_, c)| c < min_count)
+				.map(|(s, _)| s)
+				.collect();
+			if !rare.is_empty() {
+				item.retain(|s| !rare.contains(&s));
+			}
+
+			!item.items.is_empty()
+		});
+		self.measure_memory();
+	}
+}
+
+impl ChainItem {
+	/// Keeps the items for which `f` returns `true`, with their tags.
+	fn retain(&mut self, mut f: impl FnMut(Spur) -> bool) {
+		if self.tags.is_empty() {
+			self.items.retain(|&s| f(s));
+			return;
+		}
+
+		let (items, tags) = self
+			.items
+			.iter()
+			.zip(&self.tags)
+			.filter(|(&s, _)| f(s))
+			.map(|(&s, &t)| (s, t))
+			.unzip();
+		self.items = items;
+		self.tags = tags;
+	}
+}
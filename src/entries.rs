@@ -0,0 +1,62 @@
+use std::{fmt, hash::Hash, marker::PhantomData};
+
+use hashbrown::HashMap;
+use serde::{
+	de::{SeqAccess, Visitor},
+	Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// Serializes the map as a sequence of key and value pairs.
+///
+/// Unlike maps, sequences can have any type of key in every format, including the formats that are
+/// not self-describing, like bincode and postcard.
+#[inline]
+pub(crate) fn serialize<K, V, H, S>(
+	map: &HashMap<K, V, H>,
+	serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+	K: Serialize,
+	V: Serialize,
+	S: Serializer,
+{
+	serializer.collect_seq(map)
+}
+
+/// Deserializes a map that is serialized by [`serialize()`].
+#[inline]
+pub(crate) fn deserialize<'de, K, V, H, D>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+where
+	K: Deserialize<'de> + Eq + Hash,
+	V: Deserialize<'de>,
+	H: std::hash::BuildHasher + Default,
+	D: Deserializer<'de>,
+{
+	deserializer.deserialize_seq(EntriesVisitor(PhantomData))
+}
+
+struct EntriesVisitor<K, V, H>(PhantomData<HashMap<K, V, H>>);
+
+impl<'de, K, V, H> Visitor<'de> for EntriesVisitor<K, V, H>
+where
+	K: Deserialize<'de> + Eq + Hash,
+	V: Deserialize<'de>,
+	H: std::hash::BuildHasher + Default,
+{
+	type Value = HashMap<K, V, H>;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a sequence of key and value pairs")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		// the hint is limited, so a corrupted length can't allocate too much up front
+		let capacity = seq.size_hint().unwrap_or(0).min(4096);
+		let mut map = HashMap::with_capacity_and_hasher(capacity, H::default());
+		while let Some((k, v)) = seq.next_element()? {
+			map.insert(k, v);
+		}
+
+		Ok(map)
+	}
+}
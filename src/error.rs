@@ -0,0 +1,173 @@
+use std::{error::Error, fmt, hash::BuildHasher};
+
+use lasso::{Key, Spur};
+use rand::RngCore;
+
+use crate::{chain::join_tokens, LimitKind, RawMarkovChain, UnknownSeedTokens, MAX_STATE_SIZE};
+
+/// Error that is returned by the fallible methods of a [`RawMarkovChain`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MarkovError {
+	/// The configuration of the chain is not valid, like a state size of 0.
+	InvalidConfig(String),
+	/// The chain has no state, so it can't generate anything.
+	EmptyModel,
+	/// The seed has tokens that are not known by the chain.
+	UnknownSeed(UnknownSeedTokens),
+	/// Generation stopped before the requested length, as there was no way to continue.
+	/// `generated` is the number of tokens that are generated until then.
+	DeadEnd { generated: usize },
+	/// None of the sentences that are generated by [`RawMarkovChain::make_sentence()`] is within
+	/// the bounds. `tries` is the number of sentences that are generated.
+	NoSentence { tries: usize },
+	/// Training would exceed a limit of the chain, see [`Limits`](crate::Limits). The chain is
+	/// not changed.
+	LimitExceeded { kind: LimitKind, limit: usize },
+	/// A token ID is not interned by the chain, see
+	/// [`RawMarkovChain::try_add_ids()`].
+	UnknownId(Spur),
+	/// The data of the chain is not consistent, like a state that refers to a token that is not
+	/// interned. Returned while deserializing.
+	Integrity(String),
+}
+
+impl fmt::Display for MarkovError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MarkovError::InvalidConfig(message) => {
+				write!(f, "invalid configuration: {}", message)
+			}
+			MarkovError::EmptyModel => write!(f, "the chain has no state"),
+			MarkovError::UnknownSeed(e) => e.fmt(f),
+			MarkovError::DeadEnd { generated } => write!(
+				f,
+				"generation reached a dead end after {} tokens",
+				generated
+			),
+			MarkovError::NoSentence { tries } => {
+				write!(f, "no sentence is within the bounds after {} tries", tries)
+			}
+			MarkovError::LimitExceeded { kind, limit } => {
+				write!(f, "{} limit of {} is exceeded", kind, limit)
+			}
+			MarkovError::UnknownId(id) => {
+				write!(f, "token ID {} is not interned", id.into_usize())
+			}
+			MarkovError::Integrity(message) => {
+				write!(f, "corrupted chain: {}", message)
+			}
+		}
+	}
+}
+
+impl Error for MarkovError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			MarkovError::UnknownSeed(e) => Some(e),
+			_ => None,
+		}
+	}
+}
+
+impl From<UnknownSeedTokens> for MarkovError {
+	#[inline]
+	fn from(e: UnknownSeedTokens) -> Self {
+		MarkovError::UnknownSeed(e)
+	}
+}
+
+impl<const N: usize> RawMarkovChain<N> {
+	/// Does the same thing as [`RawMarkovChain::new()`], but returns an error if the state size is
+	/// 0, as chains like these don't store anything, or greater than [`MAX_STATE_SIZE`].
+	#[inline]
+	pub fn try_new(state_size: usize) -> Result<RawMarkovChain<N>, MarkovError> {
+		check_state_size(state_size)?;
+		Ok(RawMarkovChain::new(state_size))
+	}
+
+	/// Does the same thing as [`RawMarkovChain::with_capacity()`], but returns an error if the
+	/// state size is 0, as chains like these don't store anything, or greater than
+	/// [`MAX_STATE_SIZE`].
+	#[inline]
+	pub fn try_with_capacity(
+		state_size: usize,
+		capacity: usize,
+	) -> Result<RawMarkovChain<N>, MarkovError> {
+		check_state_size(state_size)?;
+		Ok(RawMarkovChain::with_capacity(state_size, capacity))
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Does the same thing as [`RawMarkovChain::generate()`], but tells the failures apart.
+	///
+	/// Returns [`MarkovError::EmptyModel`] if there is no state, and [`MarkovError::DeadEnd`] if
+	/// the text is shorter than `length`.
+	#[inline]
+	pub fn try_generate(
+		&self,
+		length: usize,
+		rng: &mut impl RngCore,
+	) -> Result<String, MarkovError> {
+		if self.is_empty() {
+			return Err(MarkovError::EmptyModel);
+		}
+
+		collect_tokens(self.iter(length, rng), length)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::generate_start()`], but tells the failures apart.
+	///
+	/// Returns [`MarkovError::EmptyModel`] if there is no state, [`MarkovError::UnknownSeed`] if
+	/// any of the last `state_size` tokens of the seed is not known by the chain, and
+	/// [`MarkovError::DeadEnd`] if the text is shorter than `length`.
+	pub fn try_generate_start<T>(
+		&self,
+		start: T,
+		length: usize,
+		rng: &mut impl RngCore,
+	) -> Result<String, MarkovError>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		if self.is_empty() {
+			return Err(MarkovError::EmptyModel);
+		}
+
+		collect_tokens(self.iter_start_strict(start, length, rng)?, length)
+	}
+}
+
+/// Returns an error if the state size is not valid.
+#[inline]
+pub(crate) fn check_state_size(state_size: usize) -> Result<(), MarkovError> {
+	if state_size == 0 {
+		return Err(MarkovError::InvalidConfig(
+			"state size must be greater than 0".to_string(),
+		));
+	}
+	if state_size > MAX_STATE_SIZE {
+		return Err(MarkovError::InvalidConfig(format!(
+			"state size must not be greater than {}",
+			MAX_STATE_SIZE
+		)));
+	}
+
+	Ok(())
+}
+
+/// Joins the tokens with spaces, returns [`MarkovError::DeadEnd`] if there are less than `length`
+/// of them.
+fn collect_tokens<'a>(
+	tokens: impl Iterator<Item = &'a str>,
+	length: usize,
+) -> Result<String, MarkovError> {
+	let mut generated = 0;
+	let res = join_tokens(tokens.inspect(|_| generated += 1));
+
+	if generated < length {
+		return Err(MarkovError::DeadEnd { generated });
+	}
+
+	Ok(res)
+}
@@ -0,0 +1,207 @@
+use std::{
+	hash::BuildHasher,
+	io::{self, Write},
+};
+
+use hashbrown::HashMap;
+use lasso::Spur;
+use smallvec::SmallVec;
+
+use crate::{RawMarkovChain, SENTENCE_END, SENTENCE_START};
+
+/// Filters that are used while exporting a [`RawMarkovChain`] as a graph.
+///
+/// Default options export the whole chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphOptions {
+	/// Maximum number of nodes to export. States with more transitions are exported first.
+	pub max_nodes: Option<usize>,
+	/// Transitions with lower probabilities than this are not exported.
+	pub min_probability: f64,
+	/// If set, only the states of this size are exported.
+	pub order: Option<usize>,
+}
+
+impl Default for GraphOptions {
+	#[inline]
+	fn default() -> Self {
+		GraphOptions {
+			max_nodes: None,
+			min_probability: 0.0,
+			order: None,
+		}
+	}
+}
+
+/// Graph representation of the chain, nodes are labeled with their tokens.
+struct Graph {
+	nodes: Vec<String>,
+	// source, target, count, probability
+	edges: Vec<(usize, usize, usize, f64)>,
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Writes the states and the transitions of the chain as a [Graphviz DOT](https://graphviz.org/doc/info/lang.html) graph.
+	///
+	/// Every transition goes from a state to the state of the same size that follows it.
+	pub fn write_dot<W: Write>(&self, mut w: W, options: &GraphOptions) -> io::Result<()> {
+		let graph = self.graph(options);
+
+		writeln!(w, "digraph markov_chain {{")?;
+		for (i, label) in graph.nodes.iter().enumerate() {
+			writeln!(w, "\tn{} [label=\"{}\"];", i, escape_dot(label))?;
+		}
+		for (src, dst, count, p) in graph.edges {
+			writeln!(
+				w,
+				"\tn{} -> n{} [label=\"{:.3}\", weight={}];",
+				src, dst, p, count
+			)?;
+		}
+		writeln!(w, "}}")
+	}
+
+	/// Writes the states and the transitions of the chain as a [GraphML](http://graphml.graphdrawing.org/) graph.
+	///
+	/// Every transition goes from a state to the state of the same size that follows it.
+	pub fn write_graphml<W: Write>(&self, mut w: W, options: &GraphOptions) -> io::Result<()> {
+		let graph = self.graph(options);
+
+		writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+		writeln!(
+			w,
+			r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+		)?;
+		writeln!(
+			w,
+			"\t<key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>"
+		)?;
+		writeln!(
+			w,
+			"\t<key id=\"count\" for=\"edge\" attr.name=\"count\" attr.type=\"long\"/>"
+		)?;
+		writeln!(
+			w,
+			"\t<key id=\"probability\" for=\"edge\" attr.name=\"probability\" attr.type=\"double\"/>"
+		)?;
+		writeln!(w, "\t<graph id=\"markov_chain\" edgedefault=\"directed\">")?;
+		for (i, label) in graph.nodes.iter().enumerate() {
+			writeln!(
+				w,
+				"\t\t<node id=\"n{}\"><data key=\"label\">{}</data></node>",
+				i,
+				escape_xml(label)
+			)?;
+		}
+		for (src, dst, count, p) in graph.edges {
+			writeln!(
+				w,
+				"\t\t<edge source=\"n{}\" target=\"n{}\"><data key=\"count\">{}</data><data key=\"probability\">{}</data></edge>",
+				src, dst, count, p
+			)?;
+		}
+		writeln!(w, "\t</graph>")?;
+		writeln!(w, "</graphml>")
+	}
+
+	/// Builds the graph that is going to be exported.
+	fn graph(&self, options: &GraphOptions) -> Graph {
+		let mut states: Vec<_> = self
+			.items
+			.iter()
+			.filter(|(k, _)| options.order.is_none_or(|o| k.len() == o))
+			.map(|(k, v)| (self.resolve_all(k), k, v))
+			.collect();
+		// most used states first, ties are broken by the tokens for a stable output
+		states.sort_unstable_by(|a, b| {
+			b.2.items
+				.len()
+				.cmp(&a.2.items.len())
+				.then_with(|| a.0.cmp(&b.0))
+		});
+		if let Some(max) = options.max_nodes {
+			states.truncate(max);
+		}
+
+		let mut ids: HashMap<SmallVec<[Spur; N]>, usize> = HashMap::new();
+		let mut nodes = Vec::with_capacity(states.len());
+		for (label, k, _) in &states {
+			ids.insert((*k).clone(), nodes.len());
+			nodes.push(label.join(" "));
+		}
+
+		let mut edges = Vec::new();
+		for (_, k, v) in &states {
+			let src = ids[*k];
+			let total = v.items.len() as f64;
+
+			for (s, count) in v.counts() {
+				let p = count as f64 / total;
+				if p < options.min_probability {
+					continue;
+				}
+
+				let mut next: SmallVec<[Spur; N]> = SmallVec::from_slice(&k[1..]);
+				next.push(s);
+
+				// states that are never followed by anything are added while there is room
+				let dst = match ids.get(&next) {
+					Some(&dst) => dst,
+					None if options
+						.max_nodes
+						.is_none_or(|m| nodes.len() < m) =>
+					{
+						nodes.push(self.resolve_all(&next).join(" "));
+						ids.insert(next, nodes.len() - 1);
+						nodes.len() - 1
+					}
+					None => continue,
+				};
+
+				edges.push((src, dst, count, p));
+			}
+		}
+
+		Graph { nodes, edges }
+	}
+
+	/// Resolves every token of the state into its label.
+	pub(crate) fn resolve_all(&self, key: &[Spur]) -> Vec<&str> {
+		key.iter()
+			.map(|s| token_label(self.cache.resolve(s)))
+			.collect()
+	}
+}
+
+/// Returns the label of the token in the exported files. Sentence markers are written as `<s>` and
+/// `</s>`, like in ARPA files, as their control characters are not allowed in XML.
+#[inline]
+pub(crate) fn token_label(s: &str) -> &str {
+	match s {
+		SENTENCE_START => "<s>",
+		SENTENCE_END => "</s>",
+		s => s,
+	}
+}
+
+/// Escapes the string to be used in a quoted DOT ID.
+fn escape_dot(s: &str) -> String {
+	s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes the string to be used in XML text.
+fn escape_xml(s: &str) -> String {
+	let mut res = String::with_capacity(s.len());
+	for c in s.chars() {
+		match c {
+			'&' => res.push_str("&amp;"),
+			'<' => res.push_str("&lt;"),
+			'>' => res.push_str("&gt;"),
+			'"' => res.push_str("&quot;"),
+			'\'' => res.push_str("&apos;"),
+			c => res.push(c),
+		}
+	}
+
+	res
+}
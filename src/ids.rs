@@ -0,0 +1,205 @@
+use std::hash::BuildHasher;
+
+use foldhash::fast::FixedState;
+use rand::RngCore;
+
+use crate::{
+	chain::{Context, Steps},
+	MarkovError, RawMarkovChain, SENTENCE_END, SENTENCE_START,
+};
+
+/// Trait for converting the IDs of the tokens into integers and back, like
+/// [`Key::into_usize()`] and [`Key::try_from_usize()`].
+pub use lasso::Key;
+/// ID of an interned token. See [`RawMarkovChain::add_ids()`].
+pub use lasso::Spur;
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns the ID of the token, which is the key that its folded form is interned with.
+	///
+	/// Returns `None` if the token is not interned.
+	#[inline]
+	pub fn token_id(&self, token: &str) -> Option<Spur> {
+		self.cache.get(self.config.normalization.apply(token))
+	}
+
+	/// Returns the token of the ID, which is its folded form.
+	///
+	/// Returns `None` if the ID is not interned.
+	#[inline]
+	pub fn id_token(&self, id: Spur) -> Option<&str> {
+		self.cache.try_resolve(&id)
+	}
+
+	/// Interns the folded form of the token and returns its ID, without adding it as training
+	/// data.
+	///
+	/// The original form of the token is not recorded, see [`RawMarkovChain::add_ids()`].
+	#[inline]
+	pub fn intern_token(&mut self, token: &str) -> Spur {
+		self.intern_folded(token)
+	}
+
+	/// Adds the IDs of the tokens as training data, without hashing any string.
+	///
+	/// IDs are created by [`RawMarkovChain::intern_token()`], or by the shared interner of the
+	/// chain. Generation outputs the original forms of the tokens that are added with
+	/// [`RawMarkovChain::add_tokens()`], and the folded forms of the rest.
+	///
+	/// IDs are not added if any of them is not interned, or if they exceed the
+	/// [`Limits`](crate::Limits) of the chain, see [`RawMarkovChain::try_add_ids()`].
+	#[inline]
+	pub fn add_ids<T>(&mut self, ids: T)
+	where
+		T: IntoIterator<Item = Spur>,
+	{
+		let _ = self.train_ids(ids, 1);
+	}
+
+	/// Does the same thing as [`RawMarkovChain::add_ids()`], but every transition is added
+	/// `weight` number of times, like [`RawMarkovChain::add_tokens_weighted()`].
+	#[inline]
+	pub fn add_ids_weighted<T>(&mut self, ids: T, weight: usize)
+	where
+		T: IntoIterator<Item = Spur>,
+	{
+		if weight == 0 {
+			return;
+		}
+
+		let _ = self.train_ids(ids, weight);
+	}
+
+	/// Does the same thing as [`RawMarkovChain::add_ids()`], but returns
+	/// [`MarkovError::UnknownId`] if any of the IDs is not interned, and
+	/// [`MarkovError::LimitExceeded`] if the IDs exceed the limits of the chain.
+	#[inline]
+	pub fn try_add_ids<T>(&mut self, ids: T) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item = Spur>,
+	{
+		self.train_ids(ids, 1)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::add_ids_weighted()`], but returns an error like
+	/// [`RawMarkovChain::try_add_ids()`].
+	#[inline]
+	pub fn try_add_ids_weighted<T>(&mut self, ids: T, weight: usize) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item = Spur>,
+	{
+		if weight == 0 {
+			return Ok(());
+		}
+
+		self.train_ids(ids, weight)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter()`], but returns the IDs of the tokens
+	/// instead of their original forms.
+	#[inline]
+	pub fn iter_ids<R: RngCore>(&self, count: usize, rng: R) -> MarkovChainIdIter<'_, N, R, H> {
+		MarkovChainIdIter {
+			chain: self,
+			steps: Steps::new(count, rng, self.id_context(None)),
+		}
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter_start()`], but starts from the IDs of the
+	/// tokens and returns the IDs of the generated ones.
+	///
+	/// Like [`RawMarkovChain::iter_start()`], only the last `state_size` IDs are used, and the ones
+	/// that are not interned are skipped.
+	#[inline]
+	pub fn iter_start_ids<T, R>(
+		&self,
+		start: T,
+		count: usize,
+		rng: R,
+	) -> MarkovChainIdIter<'_, N, R, H>
+	where
+		T: IntoIterator<Item = Spur>,
+		R: RngCore,
+	{
+		MarkovChainIdIter {
+			chain: self,
+			steps: Steps::new(count, rng, self.id_context(start)),
+		}
+	}
+
+	/// Adds the IDs as training data, every transition is added `weight` number of times.
+	///
+	/// Returns an error without changing the chain if any of the IDs is not interned, or if they
+	/// exceed the limits of the chain.
+	fn train_ids<T>(&mut self, ids: T, weight: usize) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item = Spur>,
+	{
+		let markers = if self.config.sentence_boundaries {
+			[self.cache.get(SENTENCE_START), self.cache.get(SENTENCE_END)]
+		} else {
+			[None, None]
+		};
+		let ids: Vec<_> = ids
+			.into_iter()
+			.filter(|s| !markers.contains(&Some(*s)))
+			.collect();
+
+		if let Some(&s) = ids.iter().find(|s| s.into_usize() >= self.cache.len()) {
+			return Err(MarkovError::UnknownId(s));
+		}
+		if ids.is_empty() {
+			return Ok(());
+		}
+		if !self.config.limits.is_unlimited() {
+			let tokens: Vec<_> = ids.iter().map(|s| self.cache.resolve(s)).collect();
+			self.check_limits(&tokens, weight, false)?;
+		}
+
+		ids.iter().for_each(|&s| self.add_count(s, weight));
+		self.add_transitions(ids, weight, None);
+
+		Ok(())
+	}
+
+	/// Returns the context for starting the generation from the given IDs.
+	fn id_context<T>(&self, start: T) -> Context<N>
+	where
+		T: IntoIterator<Item = Spur>,
+	{
+		let start: Vec<_> = start.into_iter().collect();
+		let mut prev = Context::new(self.state_size);
+		start[start.len().saturating_sub(self.state_size)..]
+			.iter()
+			.filter(|s| s.into_usize() < self.cache.len())
+			.for_each(|&s| prev.push(s));
+
+		if self.config.sentence_boundaries && prev.as_slice().is_empty() {
+			if let Some(start) = self.cache.get(SENTENCE_START) {
+				(0..self.state_size).for_each(|_| prev.push(start));
+			}
+		}
+
+		prev
+	}
+}
+
+/// Iterator that iterates over the IDs of the generated tokens. Created by
+/// [`RawMarkovChain::iter_ids()`].
+///
+/// Generic over the RNG, which can be either borrowed, like `&mut ThreadRng`, or owned.
+pub struct MarkovChainIdIter<'a, const N: usize, R, H = FixedState> {
+	chain: &'a RawMarkovChain<N, H>,
+	steps: Steps<N, R>,
+}
+
+impl<const N: usize, R: RngCore, H: BuildHasher> Iterator for MarkovChainIdIter<'_, N, R, H> {
+	type Item = Spur;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		let chain = self.chain;
+		self.steps
+			.step(|prev, rng| chain.next_step(prev, rng).map(|next| (next, next)))
+	}
+}
@@ -0,0 +1,168 @@
+use std::hash::BuildHasher;
+
+use hashbrown::hash_map;
+use lasso::{Key, Spur};
+use smallvec::SmallVec;
+
+use crate::{interner::Interner, ChainItem, RawMarkovChain};
+
+/// A read-only view of a state of a [`RawMarkovChain`] and its successors.
+#[derive(Clone, Copy)]
+pub struct State<'a> {
+	cache: &'a Interner,
+	key: &'a [Spur],
+	item: &'a ChainItem,
+}
+
+impl<'a> State<'a> {
+	/// Returns the tokens of the state, from the oldest to the newest.
+	#[inline]
+	pub fn tokens(&self) -> impl Iterator<Item = &'a str> + 'a {
+		let cache = self.cache;
+		self.key.iter().map(move |s| cache.resolve(s))
+	}
+
+	/// Returns the size of the state.
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.key.len()
+	}
+
+	/// Returns whether the state has no tokens. Is always `false` for the states of a chain.
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.key.is_empty()
+	}
+
+	/// Returns the total number of transitions from the state.
+	#[inline]
+	pub fn total(&self) -> usize {
+		self.item.items.len()
+	}
+
+	/// Returns the successors of the state with the number of times they are seen.
+	///
+	/// Successors are ordered by the time they are first interned.
+	pub fn successors(&self) -> Vec<(&'a str, usize)> {
+		self.item
+			.counts()
+			.into_iter()
+			.map(|(s, c)| (self.cache.resolve(&s), c))
+			.collect()
+	}
+}
+
+/// Iterator over the states of a [`RawMarkovChain`]. Created by [`RawMarkovChain::states()`].
+pub struct States<'a, const N: usize> {
+	cache: &'a Interner,
+	inner: hash_map::Iter<'a, SmallVec<[Spur; N]>, ChainItem>,
+}
+
+impl<'a, const N: usize> Iterator for States<'a, N> {
+	type Item = State<'a>;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		let (key, item) = self.inner.next()?;
+
+		Some(State {
+			cache: self.cache,
+			key,
+			item,
+		})
+	}
+
+	#[inline]
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns an iterator over every state of the chain, in arbitrary order.
+	#[inline]
+	pub fn states(&self) -> States<'_, N> {
+		States {
+			cache: &self.cache,
+			inner: self.items.iter(),
+		}
+	}
+
+	/// Returns the state that consists of the given tokens.
+	///
+	/// Returns `None` if there is no such state.
+	pub fn state<T>(&self, context: T) -> Option<State<'_>>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		let key = self.key(context)?;
+		let (key, item) = self.items.get_key_value(key.as_slice())?;
+
+		Some(State {
+			cache: &self.cache,
+			key,
+			item,
+		})
+	}
+
+	/// Returns the successors of the state that consists of the given tokens, with the number of times
+	/// they are seen.
+	///
+	/// Returns `None` if there is no such state.
+	#[inline]
+	pub fn successors<T>(&self, context: T) -> Option<Vec<(&str, usize)>>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		self.state(context).map(|s| s.successors())
+	}
+
+	/// Returns whether there is a state that consists of the given tokens.
+	#[inline]
+	pub fn contains_state<T>(&self, context: T) -> bool
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		self.key(context)
+			.is_some_and(|k| self.items.contains_key(k.as_slice()))
+	}
+
+	/// Returns the number of times the token is seen in the training data.
+	#[inline]
+	pub fn token_count(&self, token: &str) -> usize {
+		self.cache
+			.get(self.config.normalization.apply(token))
+			.and_then(|s| self.token_counts.get(s.into_usize()))
+			.copied()
+			.unwrap_or(0)
+	}
+
+	/// Returns an iterator over every token that is seen in the training data, in the order they
+	/// are interned.
+	///
+	/// Tokens that are interned without being added, like the sentence markers or the tokens of
+	/// the other chains that share the interner, are left out.
+	#[inline]
+	pub fn vocabulary(&self) -> impl Iterator<Item = &str> {
+		self.cache
+			.iter()
+			.filter(|(s, _)| {
+				self.token_counts
+					.get(s.into_usize())
+					.is_some_and(|&c| c > 0)
+			})
+			.map(|(_, t)| t)
+	}
+
+	/// Converts the tokens into a key.
+	///
+	/// Returns `None` if any of the tokens is not interned.
+	pub(crate) fn key<T>(&self, tokens: T) -> Option<SmallVec<[Spur; N]>>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		tokens.into_iter()
+			.map(|t| self.cache.get(self.config.normalization.apply(t.as_ref())))
+			.collect()
+	}
+}
@@ -0,0 +1,274 @@
+use std::{hash::BuildHasher, mem::size_of, sync::Arc};
+
+use lasso::{Key, Rodeo, Spur};
+
+use crate::{RawMarkovChain, SENTENCE_END, SENTENCE_START};
+
+#[cfg(feature = "serialize")]
+use {
+	crate::{normalize::SurfaceForms, ChainConfig, ChainItem},
+	hashbrown::HashMap,
+	serde::{Deserialize, Deserializer, Serialize, Serializer},
+	smallvec::SmallVec,
+};
+
+/// Thread-safe interner that can be shared by many chains, see
+/// [`RawMarkovChain::with_interner()`].
+pub use lasso::ThreadedRodeo;
+
+/// Interner of the tokens of a chain, which is either its own or shared with other chains.
+#[derive(Clone)]
+pub(crate) enum Interner {
+	Owned(Rodeo),
+	Shared(Arc<ThreadedRodeo>),
+}
+
+impl Default for Interner {
+	#[inline]
+	fn default() -> Self {
+		Interner::Owned(Rodeo::new())
+	}
+}
+
+impl Interner {
+	#[inline]
+	pub(crate) fn get<T: AsRef<str>>(&self, s: T) -> Option<Spur> {
+		match self {
+			Interner::Owned(r) => r.get(s),
+			Interner::Shared(r) => r.get(s),
+		}
+	}
+
+	#[inline]
+	pub(crate) fn get_or_intern<T: AsRef<str>>(&mut self, s: T) -> Spur {
+		match self {
+			Interner::Owned(r) => r.get_or_intern(s),
+			Interner::Shared(r) => r.get_or_intern(s),
+		}
+	}
+
+	#[inline]
+	pub(crate) fn resolve(&self, s: &Spur) -> &str {
+		match self {
+			Interner::Owned(r) => r.resolve(s),
+			Interner::Shared(r) => r.resolve(s),
+		}
+	}
+
+	#[inline]
+	pub(crate) fn try_resolve(&self, s: &Spur) -> Option<&str> {
+		match self {
+			Interner::Owned(r) => r.try_resolve(s),
+			Interner::Shared(r) => r.try_resolve(s),
+		}
+	}
+
+	/// Returns the number of interned strings.
+	#[inline]
+	pub(crate) fn len(&self) -> usize {
+		match self {
+			Interner::Owned(r) => r.len(),
+			Interner::Shared(r) => r.len(),
+		}
+	}
+
+	/// Returns the interned strings with their keys, in the order of the keys.
+	///
+	/// Strings of a shared interner may be interned by another thread while this is called, so
+	/// the iterator stops at the first key that is not resolved yet.
+	pub(crate) fn iter(&self) -> impl Iterator<Item = (Spur, &str)> {
+		(0..self.len()).map_while(|i| {
+			let s = Spur::try_from_usize(i)?;
+			Some((s, self.try_resolve(&s)?))
+		})
+	}
+
+	/// Returns the bytes used by the interner, including the strings themselves.
+	pub(crate) fn memory_usage(&self) -> usize {
+		let table = |capacity: usize| capacity * (size_of::<&str>() + size_of::<Spur>());
+		match self {
+			Interner::Owned(r) => r.current_memory_usage() + table(r.capacity()),
+			Interner::Shared(r) => r.current_memory_usage() + table(r.capacity()),
+		}
+	}
+}
+
+#[cfg(feature = "serialize")]
+impl Serialize for Interner {
+	/// Serializes the strings in the order of their keys, like [`Rodeo`] does.
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Interner::Owned(r) => r.serialize(serializer),
+			Interner::Shared(_) => serializer.collect_seq(self.iter().map(|(_, s)| s)),
+		}
+	}
+}
+
+#[cfg(feature = "serialize")]
+impl<'de> Deserialize<'de> for Interner {
+	/// Deserializes the strings into an interner that is owned by the chain.
+	#[inline]
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		Rodeo::deserialize(deserializer).map(Interner::Owned)
+	}
+}
+
+impl<const N: usize> RawMarkovChain<N> {
+	/// Creates an empty MarkovChain, which interns its tokens in the given shared interner.
+	///
+	/// Chains that share an interner store every token once, and a token has the same key in all
+	/// of them. The chain still keeps its own counts, original forms and tags.
+	///
+	/// Chains are serialized with the tokens that they use, and deserialized with their own
+	/// interners. [`Limits::max_vocabulary`](crate::Limits) sees every token of the shared
+	/// interner, including the ones of the other chains.
+	#[inline]
+	pub fn with_interner(state_size: usize, interner: Arc<ThreadedRodeo>) -> RawMarkovChain<N> {
+		let mut chain = RawMarkovChain::new(state_size);
+		chain.cache = Interner::Shared(interner);
+		chain.measure_memory();
+
+		chain
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns the shared interner of the chain, or `None` if the chain has its own interner.
+	#[inline]
+	pub fn shared_interner(&self) -> Option<&Arc<ThreadedRodeo>> {
+		match &self.cache {
+			Interner::Owned(_) => None,
+			Interner::Shared(r) => Some(r),
+		}
+	}
+
+	/// Returns `true` if both chains use the same shared interner, so that their tokens have the
+	/// same keys.
+	#[inline]
+	pub fn shares_interner<const M: usize, H2>(&self, other: &RawMarkovChain<M, H2>) -> bool {
+		match (&self.cache, &other.cache) {
+			(Interner::Shared(a), Interner::Shared(b)) => Arc::ptr_eq(a, b),
+			_ => false,
+		}
+	}
+
+	/// Returns the tokens that the chain uses with their keys, in the order of the keys. These
+	/// are the tokens that are seen in the training data, and the sentence markers.
+	pub(crate) fn used_tokens(&self) -> impl Iterator<Item = (Spur, &str)> {
+		let boundaries = self.config.sentence_boundaries;
+		self.cache.iter().filter(move |&(s, t)| {
+			self.token_counts
+				.get(s.into_usize())
+				.is_some_and(|&c| c > 0) || (boundaries
+				&& (t == SENTENCE_START || t == SENTENCE_END))
+		})
+	}
+
+	/// Returns whether each key of the interner is used by the chain, which is the case for the
+	/// tokens of [`RawMarkovChain::used_tokens()`] and the tokens in the states.
+	pub(crate) fn referenced_tokens(&self) -> Vec<bool> {
+		// states may refer to the tokens that are not counted, so they are kept too
+		let mut used = vec![false; self.cache.len()];
+		for (s, _) in self.used_tokens() {
+			used[s.into_usize()] = true;
+		}
+		for (k, v) in &self.items {
+			for s in k.iter().chain(&v.items) {
+				used[s.into_usize()] = true;
+			}
+		}
+
+		used
+	}
+}
+
+/// Layout of a serialized [`RawMarkovChain`].
+#[cfg(feature = "serialize")]
+#[derive(Serialize)]
+#[serde(rename = "RawMarkovChain")]
+struct ChainLayout<'a, I, C> {
+	items: I,
+	state_size: usize,
+	cache: C,
+	token_counts: &'a [usize],
+	config: &'a ChainConfig,
+	surfaces: &'a [SurfaceForms],
+	tag_cache: &'a Rodeo,
+}
+
+/// States of a chain, which are serialized as a sequence of key and value pairs.
+#[cfg(feature = "serialize")]
+struct Entries<'a, const N: usize, H>(&'a HashMap<SmallVec<[Spur; N]>, ChainItem, H>);
+
+#[cfg(feature = "serialize")]
+impl<const N: usize, H> Serialize for Entries<'_, N, H> {
+	#[inline]
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		crate::entries::serialize(self.0, serializer)
+	}
+}
+
+#[cfg(feature = "serialize")]
+impl<const N: usize, H: BuildHasher> Serialize for RawMarkovChain<N, H> {
+	/// Serializes the chain. A chain with a shared interner is serialized with the tokens that it
+	/// uses only, which are renumbered in the order of their keys, so the file doesn't hold the
+	/// tokens of the other chains.
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		if let Interner::Owned(cache) = &self.cache {
+			return ChainLayout {
+				items: Entries(&self.items),
+				state_size: self.state_size,
+				cache,
+				token_counts: &self.token_counts,
+				config: &self.config,
+				surfaces: &self.surfaces,
+				tag_cache: &self.tag_cache,
+			}
+			.serialize(serializer);
+		}
+
+		let used = self.referenced_tokens();
+
+		// old key to new key, the order of the keys is kept so generation doesn't change
+		let mut cache = Rodeo::new();
+		let mut keys = vec![Spur::default(); used.len()];
+		let mut token_counts = Vec::new();
+		let mut surfaces = Vec::new();
+		for (s, t) in self.cache.iter().filter(|(s, _)| used[s.into_usize()]) {
+			let i = s.into_usize();
+			keys[i] = cache.get_or_intern(t);
+			token_counts.push(self.token_counts.get(i).copied().unwrap_or(0));
+			if !self.surfaces.is_empty() {
+				surfaces.push(self.surfaces.get(i).cloned().unwrap_or_default());
+			}
+		}
+
+		let items: Vec<_> = self
+			.items
+			.iter()
+			.map(|(k, v)| {
+				let key: SmallVec<[Spur; N]> =
+					k.iter().map(|s| keys[s.into_usize()]).collect();
+				let item = ChainItem {
+					items: v.items
+						.iter()
+						.map(|s| keys[s.into_usize()])
+						.collect(),
+					tags: v.tags.clone(),
+				};
+				(key, item)
+			})
+			.collect();
+
+		ChainLayout {
+			items,
+			state_size: self.state_size,
+			cache: &cache,
+			token_counts: &token_counts,
+			config: &self.config,
+			surfaces: &surfaces,
+			tag_cache: &self.tag_cache,
+		}
+		.serialize(serializer)
+	}
+}
@@ -5,13 +5,17 @@
 //!
 //! - Takes `IntoIterator<Item: AsRef<str>>` as an argument, instead of regex and &str
 //! - MarkovChain::generate and MarkovChain::generate_start functions both take RngCore instead of using rand::thread_rng().
-//! - Strings are interned for faster training and less memory usage.
+//! - Strings are interned for faster training and less memory usage, optionally in an interner that is shared by many chains.
+//! - Configurable sentence boundaries, state orders, fallback and pruning with `ChainBuilder`.
+//! - Optional case, diacritic and Unicode normalization of the tokens, while generation keeps their original forms.
 //! - Serialization and deserialization with [serde](https://docs.rs/serde/latest/serde/), when `serialize` feature flag is used.
+//! - Owned generation `Stream`s for async code, when `stream` feature flag is used.
+//! - Parallel batch generation with [rayon](https://docs.rs/rayon/latest/rayon/), when `rayon` feature flag is used.
 //!
 //! Example
 //! -------
 //!
-//! ```rust
+//! ```rust,no_run
 //! use markov_str::*;
 //! use rand::SeedableRng;
 //! use regex::Regex;
@@ -100,8 +104,52 @@
 //!
 //! markov_str is licensed under the MIT license. Feel free to fork and use however you like.
 
+mod arpa;
+#[cfg(feature = "rayon")]
+mod batch;
+mod canonical;
 mod chain;
+mod config;
+#[cfg(feature = "serialize")]
+mod entries;
+mod error;
+mod export;
+mod ids;
+mod inspect;
+mod interner;
+mod limits;
+mod mixture;
+mod normalize;
+mod owned;
+mod seed;
+mod sentence;
+mod stats;
+mod tags;
+mod trace;
+mod validate;
+#[cfg(feature = "serialize")]
+mod version;
+pub use crate::arpa::*;
+#[cfg(feature = "serialize")]
+pub use crate::canonical::*;
 pub use crate::chain::*;
+pub use crate::config::*;
+pub use crate::error::*;
+pub use crate::export::*;
+pub use crate::ids::*;
+pub use crate::inspect::*;
+pub use crate::interner::*;
+pub use crate::limits::*;
+pub use crate::mixture::*;
+pub use crate::normalize::Normalization;
+pub use crate::owned::*;
+pub use crate::seed::*;
+pub use crate::sentence::*;
+pub use crate::stats::*;
+pub use crate::tags::*;
+pub use crate::trace::*;
+#[cfg(feature = "serialize")]
+pub use crate::version::*;
 
 /// Recommended Regex for general use.
 pub static WORD_REGEX: &str = r"(\p{Alphabetic}|\d)(\p{Alphabetic}|\d|'|-)*(\.|!|\?)?";
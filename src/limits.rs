@@ -0,0 +1,288 @@
+use std::{fmt, hash::BuildHasher, mem::size_of};
+
+use hashbrown::{HashMap, HashSet};
+use lasso::{Key, Spur};
+use smallvec::SmallVec;
+
+use crate::{
+	chain::for_each_transition, ChainItem, MarkovError, RawMarkovChain, SENTENCE_END,
+	SENTENCE_START,
+};
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Limits of the resources that training can use, for training with untrusted input. Stored in
+/// [`ChainConfig::limits`](crate::ChainConfig).
+///
+/// Training that would exceed a limit doesn't change the chain. The fallible methods, like
+/// [`RawMarkovChain::try_add_tokens()`], report it with [`MarkovError::LimitExceeded`], and the
+/// others skip the training data silently.
+///
+/// Default limits don't limit anything.
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize), serde(default))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Limits {
+	/// Maximum length of a token, in bytes.
+	pub max_token_len: Option<usize>,
+	/// Maximum number of distinct tokens.
+	pub max_vocabulary: Option<usize>,
+	/// Maximum number of states.
+	pub max_states: Option<usize>,
+	/// Maximum weight of the training data.
+	pub max_weight: Option<usize>,
+	/// Maximum memory usage, in bytes. Memory usage is measured like
+	/// [`RawMarkovChain::memory_usage()`] when the chain is created, deserialized, pruned or
+	/// canonicalized, and the training data that is added after that is estimated, so checking it is
+	/// cheap.
+	pub max_memory: Option<usize>,
+}
+
+impl Limits {
+	/// Returns `true` if none of the limits is set.
+	#[inline]
+	pub fn is_unlimited(&self) -> bool {
+		*self == Limits::default()
+	}
+}
+
+/// Kinds of the limits in [`Limits`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+	/// [`Limits::max_token_len`].
+	TokenLength,
+	/// [`Limits::max_vocabulary`].
+	Vocabulary,
+	/// [`Limits::max_states`].
+	States,
+	/// [`Limits::max_weight`].
+	Weight,
+	/// [`Limits::max_memory`].
+	Memory,
+}
+
+impl fmt::Display for LimitKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let name = match self {
+			LimitKind::TokenLength => "token length",
+			LimitKind::Vocabulary => "vocabulary",
+			LimitKind::States => "states",
+			LimitKind::Weight => "weight",
+			LimitKind::Memory => "memory",
+		};
+
+		f.write_str(name)
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Does the same thing as [`RawMarkovChain::add_tokens()`], but returns
+	/// [`MarkovError::LimitExceeded`] if the tokens exceed the [`Limits`] of the chain.
+	#[inline]
+	pub fn try_add_tokens<T>(&mut self, tokens: T) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		self.train(tokens, 1, None)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::add_tokens_weighted()`], but returns
+	/// [`MarkovError::LimitExceeded`] if the tokens or the weight exceed the [`Limits`] of the
+	/// chain.
+	#[inline]
+	pub fn try_add_tokens_weighted<T>(
+		&mut self,
+		tokens: T,
+		weight: usize,
+	) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		if weight == 0 {
+			return Ok(());
+		}
+
+		self.train(tokens, weight, None)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::add_tokens_tagged()`], but returns
+	/// [`MarkovError::LimitExceeded`] if the tokens exceed the [`Limits`] of the chain.
+	#[inline]
+	pub fn try_add_tokens_tagged<T>(&mut self, tokens: T, tag: &str) -> Result<(), MarkovError>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		self.train(tokens, 1, Some(tag))
+	}
+
+	/// Does the same thing as [`RawMarkovChain::add_text()`], but returns
+	/// [`MarkovError::LimitExceeded`] if the text exceeds the [`Limits`] of the chain.
+	///
+	/// If the text is split into sentences, the sentences before the one that exceeds the limits
+	/// are kept.
+	pub fn try_add_text(&mut self, text: &str) -> Result<(), MarkovError> {
+		let tokens = self.config.tokenizer.tokenize(text);
+
+		if self.config.sentence_boundaries {
+			for sentence in tokens.split_inclusive(|t| t.ends_with(['.', '!', '?'])) {
+				self.try_add_tokens(sentence)?;
+			}
+
+			Ok(())
+		} else {
+			self.try_add_tokens(tokens)
+		}
+	}
+
+	/// Returns an error if training with the tokens would exceed the limits.
+	pub(crate) fn check_limits<T: AsRef<str>>(
+		&self,
+		tokens: &[T],
+		weight: usize,
+		tagged: bool,
+	) -> Result<(), MarkovError> {
+		let limits = &self.config.limits;
+		check(limits.max_weight, weight, LimitKind::Weight)?;
+		if let Some(t) = tokens.iter().map(|t| t.as_ref().len()).max() {
+			check(limits.max_token_len, t, LimitKind::TokenLength)?;
+		}
+
+		// tokens that are not interned yet are numbered after the interned ones
+		let interned = self.cache.len();
+		let mut new_tokens: HashMap<String, usize> = HashMap::new();
+		let mut new_bytes = 0;
+		let mut id = |t: &str| {
+			let t = self.config.normalization.apply(t);
+			if let Some(s) = self.cache.get(&t) {
+				return s.into_usize();
+			}
+
+			let next = interned + new_tokens.len();
+			*new_tokens.entry_ref(t.as_ref()).or_insert_with(|| {
+				new_bytes += t.len();
+				next
+			})
+		};
+
+		let mut ids: Vec<usize> = Vec::with_capacity(tokens.len() + self.state_size + 1);
+		if self.config.sentence_boundaries {
+			let start = id(SENTENCE_START);
+			ids.extend(std::iter::repeat_n(start, self.state_size));
+		}
+		ids.extend(tokens.iter().map(|t| id(t.as_ref())));
+		if self.config.sentence_boundaries {
+			ids.push(id(SENTENCE_END));
+		}
+		check(
+			limits.max_vocabulary,
+			interned + new_tokens.len(),
+			LimitKind::Vocabulary,
+		)?;
+
+		let mut new_states: HashSet<SmallVec<[usize; N]>> = HashSet::new();
+		let mut transitions: usize = 0;
+		for_each_transition(&ids, self.state_size, self.config.min_order, |slice, _| {
+			transitions += 1;
+
+			let key: Option<SmallVec<[Spur; N]>> =
+				slice.iter()
+					.map(|&i| {
+						(i < interned)
+							.then(|| Spur::try_from_usize(i))
+							.flatten()
+					})
+					.collect();
+			if key.is_none_or(|k| !self.items.contains_key(k.as_slice())) {
+				new_states.insert(SmallVec::from_slice(slice));
+			}
+		});
+		check(
+			limits.max_states,
+			self.items.len() + new_states.len(),
+			LimitKind::States,
+		)?;
+
+		if limits.max_memory.is_some() {
+			// original forms are only recorded if the tokens are folded
+			let mut new_surfaces: usize = 0;
+			if !self.config.normalization.is_none() {
+				let mut seen = HashSet::new();
+				for t in tokens.iter().map(|t| t.as_ref()) {
+					let known = self
+						.cache
+						.get(self.config.normalization.apply(t))
+						.and_then(|s| self.surfaces.get(s.into_usize()))
+						.is_some_and(|forms| {
+							forms.iter().any(|(s, _)| s == t)
+						});
+					if !known && seen.insert(t) {
+						new_surfaces +=
+							size_of::<(String, usize)>() + t.len();
+					}
+				}
+			}
+
+			let successor = successor_bytes(tagged || !self.tag_cache.is_empty());
+			let added = new_states
+				.iter()
+				.map(|k| state_bytes::<N>(k.len()))
+				.fold(0, usize::saturating_add)
+				.saturating_add(
+					transitions
+						.saturating_mul(weight)
+						.saturating_mul(successor),
+				)
+				.saturating_add(new_bytes)
+				.saturating_add(new_tokens.len() * token_bytes(0))
+				.saturating_add(new_surfaces);
+
+			check(
+				limits.max_memory,
+				self.memory.saturating_add(added),
+				LimitKind::Memory,
+			)?;
+		}
+
+		Ok(())
+	}
+
+	/// Measures the memory usage of the chain, which is updated by the estimates of the training
+	/// data that is added after this.
+	#[inline]
+	pub(crate) fn measure_memory(&mut self) {
+		self.memory = self.memory_usage().total();
+	}
+}
+
+/// Returns the estimated bytes of a new state with `len` tokens.
+#[inline]
+pub(crate) fn state_bytes<const N: usize>(len: usize) -> usize {
+	let spilled = if len > N { len * size_of::<Spur>() } else { 0 };
+	// the control byte of the hash table
+	size_of::<SmallVec<[Spur; N]>>() + size_of::<ChainItem>() + 1 + spilled
+}
+
+/// Returns the estimated bytes of a successor, with its tag if `tagged` is set.
+#[inline]
+pub(crate) fn successor_bytes(tagged: bool) -> usize {
+	if tagged {
+		size_of::<Spur>() + size_of::<Option<Spur>>()
+	} else {
+		size_of::<Spur>()
+	}
+}
+
+/// Returns the estimated bytes of a new token with `len` bytes, in the interner and the counts.
+#[inline]
+pub(crate) fn token_bytes(len: usize) -> usize {
+	len + size_of::<&str>() + size_of::<Spur>() + size_of::<usize>()
+}
+
+/// Returns an error if `value` is greater than `limit`.
+#[inline]
+fn check(limit: Option<usize>, value: usize, kind: LimitKind) -> Result<(), MarkovError> {
+	match limit {
+		Some(limit) if value > limit => Err(MarkovError::LimitExceeded { kind, limit }),
+		_ => Ok(()),
+	}
+}
@@ -0,0 +1,278 @@
+use std::{borrow::Cow, collections::VecDeque, hash::BuildHasher};
+
+use foldhash::fast::FixedState;
+use rand::{Rng, RngCore};
+use smallvec::SmallVec;
+
+use crate::{ChainItem, RawMarkovChain, SENTENCE_START};
+
+/// A weighted mixture of several [`RawMarkovChain`]s, which generates text from all of them
+/// without retraining.
+///
+/// Every token is sampled from the weighted union of the successor distributions of the chains
+/// that know the current context. Every chain backs off to its longest state that matches the
+/// context, and chains that match nothing are left out of the step. If no chain matches, a chain is
+/// picked by its weight and falls back to a random state. Generation stops at the end of a
+/// sentence of a chain with [`ChainConfig::sentence_boundaries`](crate::ChainConfig).
+///
+/// Weights can be changed between the calls. Chains with weights that are not positive, or not
+/// finite, are not used.
+#[derive(Clone, Default)]
+pub struct ChainMixture<'a, const N: usize, H = FixedState> {
+	chains: Vec<(&'a RawMarkovChain<N, H>, f64)>,
+}
+
+impl<'a, const N: usize, H: BuildHasher> ChainMixture<'a, N, H> {
+	/// Creates an empty mixture.
+	#[inline]
+	pub fn new() -> ChainMixture<'a, N, H> {
+		ChainMixture { chains: Vec::new() }
+	}
+
+	/// Adds the chain with the given weight.
+	#[inline]
+	pub fn with(
+		mut self,
+		chain: &'a RawMarkovChain<N, H>,
+		weight: f64,
+	) -> ChainMixture<'a, N, H> {
+		self.push(chain, weight);
+		self
+	}
+
+	/// Adds the chain with the given weight.
+	#[inline]
+	pub fn push(&mut self, chain: &'a RawMarkovChain<N, H>, weight: f64) {
+		self.chains.push((chain, weight));
+	}
+
+	/// Sets the weight of the chain at `index`, in the order they are added.
+	///
+	/// # Panics
+	///
+	/// Panics if `index` is out of bounds.
+	#[inline]
+	pub fn set_weight(&mut self, index: usize, weight: f64) {
+		self.chains[index].1 = weight;
+	}
+
+	/// Returns the weights of the chains, in the order they are added.
+	#[inline]
+	pub fn weights(&self) -> impl Iterator<Item = f64> + '_ {
+		self.chains.iter().map(|(_, w)| *w)
+	}
+
+	/// Returns the number of chains.
+	#[inline]
+	pub fn len(&self) -> usize {
+		self.chains.len()
+	}
+
+	/// Returns `true` if there is no chain.
+	#[inline]
+	pub fn is_empty(&self) -> bool {
+		self.chains.is_empty()
+	}
+
+	/// Generates text of given length.
+	/// First state is choosen randomly.
+	///
+	/// Returns `None` if none of the chains with positive weights has a state.
+	#[inline]
+	pub fn generate(&self, length: usize, rng: &mut impl RngCore) -> Option<String> {
+		self.generate_start(None::<&str>, length, rng)
+	}
+
+	/// Generates text of given length, starting from the given tokens.
+	///
+	/// Returns `None` if none of the chains with positive weights has a state.
+	pub fn generate_start<T>(
+		&self,
+		start: T,
+		length: usize,
+		rng: &mut impl RngCore,
+	) -> Option<String>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		if self.active().all(|(c, _)| c.is_empty()) {
+			return None;
+		}
+
+		let mut res = String::new();
+		for next in self.iter_start(start, length, rng) {
+			res.push_str(next);
+			res.push(' ');
+		}
+		res.pop();
+
+		Some(res)
+	}
+
+	/// Does the same thing as [`ChainMixture::generate()`] but instead of returning a String,
+	/// returns a lazily evaluated iterator.
+	#[inline]
+	pub fn iter<R: RngCore>(&self, count: usize, rng: R) -> MixtureIter<'_, 'a, N, R, H> {
+		self.iter_start(None::<&str>, count, rng)
+	}
+
+	/// Does the same thing as [`ChainMixture::generate_start()`] but instead of returning a
+	/// String, returns a lazily evaluated iterator.
+	///
+	/// Start tokens are kept as strings, since every chain has its own interner.
+	pub fn iter_start<T, R>(
+		&self,
+		start: T,
+		count: usize,
+		rng: R,
+	) -> MixtureIter<'_, 'a, N, R, H>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		let size = self
+			.chains
+			.iter()
+			.map(|(c, _)| c.state_size)
+			.max()
+			.unwrap_or(0);
+
+		let mut prev = VecDeque::with_capacity(size + 1);
+		for s in start {
+			prev.push_back(Cow::Owned(s.as_ref().to_string()));
+			if prev.len() > size {
+				let _ = prev.pop_front();
+			}
+		}
+		// chains with sentence boundaries start from the start of a sentence
+		if prev.is_empty()
+			&& self.chains
+				.iter()
+				.any(|(c, _)| c.config.sentence_boundaries)
+		{
+			prev.extend(std::iter::repeat_n(Cow::Borrowed(SENTENCE_START), size));
+		}
+
+		MixtureIter {
+			mixture: self,
+			count,
+			rng,
+			prev,
+			size,
+		}
+	}
+
+	/// Returns the chains with positive and finite weights.
+	#[inline]
+	fn active(&self) -> impl Iterator<Item = (&'a RawMarkovChain<N, H>, f64)> + '_ {
+		self.chains
+			.iter()
+			.copied()
+			.filter(|&(_, w)| w > 0.0 && w.is_finite())
+	}
+
+	/// Returns the next token for the given previous tokens.
+	///
+	/// Returns `None` if none of the chains with positive weights has a state.
+	fn next_step(
+		&self,
+		prev: &VecDeque<Cow<'_, str>>,
+		rng: &mut impl RngCore,
+	) -> Option<&'a str> {
+		let matches: Vec<_> = self
+			.active()
+			.filter_map(|(c, w)| Some(((c, Some(c.longest_state(prev)?)), w)))
+			.collect();
+		let (chain, item) = if matches.is_empty() {
+			let nonempty: Vec<_> = self
+				.active()
+				.filter(|(c, _)| !c.is_empty())
+				.map(|(c, w)| ((c, None), w))
+				.collect();
+			pick(&nonempty, rng)?
+		} else {
+			pick(&matches, rng)?
+		};
+
+		let next = match item {
+			Some(item) => item.get_rand(rng)?,
+			None => chain.fallback(rng)?,
+		};
+		if chain.is_sentence_end(next) {
+			return None;
+		}
+
+		Some(chain.resolve_surface(next))
+	}
+}
+
+/// Picks an item in proportion to its weight. Weights must be positive and finite.
+fn pick<T: Copy>(items: &[(T, f64)], rng: &mut impl RngCore) -> Option<T> {
+	if items.is_empty() {
+		return None;
+	}
+	// weights are scaled down to at most 1, so that their sum can't overflow
+	let max = items.iter().map(|&(_, w)| w).fold(0.0, f64::max);
+	let total: f64 = items.iter().map(|(_, w)| w / max).sum();
+
+	let mut r = rng.gen_range(0.0..total);
+	for &(item, w) in items {
+		let w = w / max;
+		if r < w {
+			return Some(item);
+		}
+		r -= w;
+	}
+
+	// the sum may be off due to rounding
+	items.last().map(|&(item, _)| item)
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns the longest state that matches the end of the tokens.
+	fn longest_state(&self, tokens: &VecDeque<Cow<'_, str>>) -> Option<&ChainItem> {
+		// the known tokens at the end, from the newest to the oldest
+		let mut key: SmallVec<[_; N]> = tokens
+			.iter()
+			.rev()
+			.take(self.state_size)
+			.map_while(|t| self.cache.get(self.config.normalization.apply(t)))
+			.collect();
+		key.reverse();
+
+		(0..key.len()).find_map(|i| self.items.get(&key[i..]))
+	}
+}
+
+/// Iterator that iterates over generation steps of a [`ChainMixture`].
+///
+/// Generic over the RNG, which can be either borrowed, like `&mut ThreadRng`, or owned.
+pub struct MixtureIter<'m, 'a, const N: usize, R, H = FixedState> {
+	mixture: &'m ChainMixture<'a, N, H>,
+	count: usize,
+	rng: R,
+	prev: VecDeque<Cow<'a, str>>,
+	size: usize,
+}
+
+impl<'a, const N: usize, R: RngCore, H: BuildHasher> Iterator for MixtureIter<'_, 'a, N, R, H> {
+	type Item = &'a str;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.count == 0 {
+			return None;
+		}
+		self.count -= 1;
+
+		let next = self.mixture.next_step(&self.prev, &mut self.rng)?;
+
+		if self.size > 0 {
+			if self.prev.len() == self.size {
+				let _ = self.prev.pop_front();
+			}
+			self.prev.push_back(Cow::Borrowed(next));
+		}
+
+		Some(next)
+	}
+}
@@ -0,0 +1,143 @@
+use std::{borrow::Cow, hash::BuildHasher, mem::size_of};
+
+use lasso::{Key, Spur};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::RawMarkovChain;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Rules for folding tokens into the keys that a [`RawMarkovChain`] matches on.
+///
+/// Tokens that fold into the same key are treated as the same token while training and while
+/// matching the seeds, but generation outputs the most common original form of every token. The
+/// introspection and export methods show the folded keys.
+///
+/// Default rules don't change the tokens.
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Normalization {
+	/// Converts the tokens into lowercase.
+	pub case: bool,
+	/// Removes the diacritics, such as the accents, from the tokens.
+	pub diacritics: bool,
+	/// Converts the tokens into [Unicode Normalization Form C](https://unicode.org/reports/tr15/),
+	/// so that the composed and the decomposed forms of the same text are equal.
+	pub nfc: bool,
+}
+
+impl Normalization {
+	/// Rules that don't change the tokens.
+	pub const NONE: Normalization = Normalization {
+		case: false,
+		diacritics: false,
+		nfc: false,
+	};
+
+	/// Rules that apply every kind of folding.
+	pub const ALL: Normalization = Normalization {
+		case: true,
+		diacritics: true,
+		nfc: true,
+	};
+
+	/// Returns `true` if the rules don't change the tokens.
+	#[inline]
+	pub fn is_none(&self) -> bool {
+		*self == Normalization::NONE
+	}
+
+	/// Folds the token according to the rules.
+	pub fn apply<'a>(&self, token: &'a str) -> Cow<'a, str> {
+		let mut res = Cow::Borrowed(token);
+
+		// ASCII text is already in NFC and has no diacritics
+		if self.diacritics && !res.is_ascii() {
+			res = Cow::Owned(
+				res.nfd().filter(|&c| !is_combining_mark(c)).nfc().collect(),
+			);
+		} else if self.nfc && !res.is_ascii() {
+			res = Cow::Owned(res.nfc().collect());
+		}
+		if self.case && res.chars().any(|c| c.is_uppercase()) {
+			res = Cow::Owned(res.to_lowercase());
+		}
+
+		res
+	}
+}
+
+/// Original forms of a token, with the number of times they are seen.
+pub(crate) type SurfaceForms = Vec<(String, usize)>;
+
+impl<const N: usize> RawMarkovChain<N> {
+	/// Creates an empty MarkovChain, which folds the tokens with the given rules.
+	///
+	/// See [`Normalization`] for details.
+	#[inline]
+	pub fn with_normalization(
+		state_size: usize,
+		normalization: Normalization,
+	) -> RawMarkovChain<N> {
+		let mut chain = RawMarkovChain::new(state_size);
+		chain.config.normalization = normalization;
+
+		chain
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns the rules that the tokens are folded with.
+	#[inline]
+	pub fn normalization(&self) -> Normalization {
+		self.config.normalization
+	}
+
+	/// Returns the form of the token that is output by the generation, which is its most common
+	/// original form.
+	///
+	/// Returns `None` if the token is not known.
+	#[inline]
+	pub fn surface_form(&self, token: &str) -> Option<&str> {
+		let spur = self.cache.get(self.config.normalization.apply(token))?;
+		Some(self.resolve_surface(spur))
+	}
+
+	/// Returns the most common original form of the token. Ties are broken by the order of the
+	/// forms, so that the result doesn't depend on the order of the training data.
+	#[inline]
+	pub(crate) fn resolve_surface(&self, spur: Spur) -> &str {
+		self.surfaces
+			.get(spur.into_usize())
+			.and_then(|forms| {
+				forms.iter()
+					.max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+			})
+			.map_or_else(|| self.cache.resolve(&spur), |(s, _)| s.as_str())
+	}
+
+	/// Records that `token` is seen as a form of the interned `spur`, `weight` times. Does nothing
+	/// if the tokens are not folded.
+	pub(crate) fn add_surface(&mut self, spur: Spur, token: &str, weight: usize) {
+		if self.config.normalization.is_none() {
+			return;
+		}
+
+		let idx = spur.into_usize();
+		if idx >= self.surfaces.len() {
+			self.surfaces.resize_with(idx + 1, Vec::new);
+		}
+
+		let forms = &mut self.surfaces[idx];
+		match forms.iter_mut().find(|(s, _)| s == token) {
+			Some((_, c)) => *c = c.saturating_add(weight),
+			None => {
+				forms.push((token.to_string(), weight));
+				self.memory = self
+					.memory
+					.saturating_add(size_of::<(String, usize)>() + token.len());
+			}
+		}
+	}
+}
@@ -0,0 +1,133 @@
+use std::{hash::BuildHasher, sync::Arc};
+
+use foldhash::fast::FixedState;
+use rand::RngCore;
+
+use crate::{chain::Steps, RawMarkovChain};
+
+#[cfg(feature = "stream")]
+use {
+	futures_core::Stream,
+	std::{
+		pin::Pin,
+		task::{self, Poll},
+	},
+};
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Does the same thing as [`RawMarkovChain::iter()`], but the iterator owns a reference to the
+	/// chain. It can be moved into spawned tasks and threads when `rng` is owned.
+	#[inline]
+	pub fn iter_owned<R: RngCore>(
+		self: &Arc<Self>,
+		count: usize,
+		rng: R,
+	) -> OwnedMarkovChainIter<N, R, H> {
+		OwnedMarkovChainIter {
+			chain: Arc::clone(self),
+			steps: Steps::new(count, rng, self.start_context(None::<&str>)),
+		}
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter_start()`], but the iterator owns a reference
+	/// to the chain. It can be moved into spawned tasks and threads when `rng` is owned.
+	#[inline]
+	pub fn iter_start_owned<T, R>(
+		self: &Arc<Self>,
+		start: T,
+		count: usize,
+		rng: R,
+	) -> OwnedMarkovChainIter<N, R, H>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		OwnedMarkovChainIter {
+			chain: Arc::clone(self),
+			steps: Steps::new(count, rng, self.start_context(start)),
+		}
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter_owned()`], but returns a
+	/// [`Stream`](futures_core::Stream). Requires `stream` feature.
+	///
+	/// Tokens are ready as soon as they are polled, pacing can be added with the utilities of the
+	/// async runtime.
+	#[cfg(feature = "stream")]
+	#[inline]
+	pub fn stream<R: RngCore>(
+		self: &Arc<Self>,
+		count: usize,
+		rng: R,
+	) -> MarkovChainStream<N, R, H> {
+		MarkovChainStream {
+			iter: self.iter_owned(count, rng),
+		}
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter_start_owned()`], but returns a
+	/// [`Stream`](futures_core::Stream). Requires `stream` feature.
+	///
+	/// Tokens are ready as soon as they are polled, pacing can be added with the utilities of the
+	/// async runtime.
+	#[cfg(feature = "stream")]
+	#[inline]
+	pub fn stream_start<T, R>(
+		self: &Arc<Self>,
+		start: T,
+		count: usize,
+		rng: R,
+	) -> MarkovChainStream<N, R, H>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		MarkovChainStream {
+			iter: self.iter_start_owned(start, count, rng),
+		}
+	}
+}
+
+/// Iterator that iterates over generation steps, while sharing the ownership of the chain.
+pub struct OwnedMarkovChainIter<const N: usize, R, H = FixedState> {
+	chain: Arc<RawMarkovChain<N, H>>,
+	steps: Steps<N, R>,
+}
+
+impl<const N: usize, R: RngCore, H: BuildHasher> Iterator for OwnedMarkovChainIter<N, R, H> {
+	type Item = String;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		let chain = &self.chain;
+		self.steps.step(|prev, rng| {
+			let next = chain.next_step(prev, rng)?;
+			Some((next, chain.resolve_surface(next).to_string()))
+		})
+	}
+}
+
+/// Stream of generation steps, while sharing the ownership of the chain. Requires `stream`
+/// feature.
+#[cfg(feature = "stream")]
+pub struct MarkovChainStream<const N: usize, R, H = FixedState> {
+	iter: OwnedMarkovChainIter<N, R, H>,
+}
+
+#[cfg(feature = "stream")]
+impl<const N: usize, R: RngCore + Unpin, H: BuildHasher> Stream for MarkovChainStream<N, R, H> {
+	type Item = String;
+
+	#[inline]
+	fn poll_next(
+		mut self: Pin<&mut Self>,
+		_cx: &mut task::Context<'_>,
+	) -> Poll<Option<String>> {
+		Poll::Ready(self.iter.next())
+	}
+
+	#[inline]
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		(0, Some(self.iter.steps.count))
+	}
+}
@@ -0,0 +1,134 @@
+use std::{error::Error, fmt, hash::BuildHasher};
+
+use rand::RngCore;
+
+use crate::{
+	chain::{join_tokens, Context, Steps},
+	MarkovChainIter, RawMarkovChain,
+};
+
+/// Which tokens of a seed are known by a [`RawMarkovChain`]. Created by
+/// [`RawMarkovChain::match_seed()`].
+///
+/// Only the last `state_size` tokens of a seed are used for the generation, so the rest are not
+/// counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeedMatch {
+	/// Number of tokens that are known, which are used as the starting state.
+	pub matched: usize,
+	/// Tokens that are not known, in the order they appear in the seed.
+	pub unknown: Vec<String>,
+}
+
+impl SeedMatch {
+	/// Returns `true` if every token is known.
+	#[inline]
+	pub fn is_complete(&self) -> bool {
+		self.unknown.is_empty()
+	}
+}
+
+/// Error that is returned when a seed has tokens that are not known by the chain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownSeedTokens {
+	/// Number of tokens that are known.
+	pub matched: usize,
+	/// Tokens that are not known, in the order they appear in the seed.
+	pub tokens: Vec<String>,
+}
+
+impl fmt::Display for UnknownSeedTokens {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "unknown seed tokens: ")?;
+		for (i, t) in self.tokens.iter().enumerate() {
+			if i > 0 {
+				write!(f, ", ")?;
+			}
+			write!(f, "{:?}", t)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl Error for UnknownSeedTokens {}
+
+/// Text that is generated from a seed. Created by [`RawMarkovChain::generate_start_strict()`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SeededText {
+	/// Generated text, which is empty if the chain is empty.
+	pub text: String,
+	/// Number of seed tokens that are used as the starting state.
+	pub matched: usize,
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns which tokens of the seed are known by the chain.
+	///
+	/// [`RawMarkovChain::iter_start()`] and [`RawMarkovChain::generate_start()`] skip the unknown
+	/// tokens, and start from the rest.
+	#[inline]
+	pub fn match_seed<T>(&self, start: T) -> SeedMatch
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		self.seed_context(start).1
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter_start()`], but returns an error if any of
+	/// the last `state_size` tokens of the seed is not known by the chain.
+	pub fn iter_start_strict<T, R>(
+		&self,
+		start: T,
+		count: usize,
+		rng: R,
+	) -> Result<MarkovChainIter<'_, N, R, H>, UnknownSeedTokens>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		let (prev, _) = self.strict_context(start)?;
+
+		Ok(MarkovChainIter {
+			chain: self,
+			steps: Steps::new(count, rng, prev),
+		})
+	}
+
+	/// Does the same thing as [`RawMarkovChain::generate_start()`], but returns an error if any
+	/// of the last `state_size` tokens of the seed is not known by the chain.
+	pub fn generate_start_strict<T>(
+		&self,
+		start: T,
+		length: usize,
+		rng: &mut impl RngCore,
+	) -> Result<SeededText, UnknownSeedTokens>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		let (prev, matched) = self.strict_context(start)?;
+		let text = join_tokens(MarkovChainIter {
+			chain: self,
+			steps: Steps::new(length, rng, prev),
+		});
+
+		Ok(SeededText { text, matched })
+	}
+
+	/// Returns the context for starting the generation from the seed, and the number of the seed
+	/// tokens in it.
+	fn strict_context<T>(&self, start: T) -> Result<(Context<N>, usize), UnknownSeedTokens>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		let (prev, seed) = self.seed_context(start);
+		if !seed.is_complete() {
+			return Err(UnknownSeedTokens {
+				matched: seed.matched,
+				tokens: seed.unknown,
+			});
+		}
+
+		Ok((prev, seed.matched))
+	}
+}
@@ -0,0 +1,122 @@
+use std::hash::BuildHasher;
+
+use rand::RngCore;
+
+use crate::{MarkovError, RawMarkovChain};
+
+/// Bounds of the sentences that are made by [`RawMarkovChain::make_sentence()`].
+///
+/// Bounds are inclusive. Lengths in characters count the spaces between the tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SentenceOptions {
+	/// Minimum number of tokens. Default is 1.
+	pub min_tokens: usize,
+	/// Maximum number of tokens. Generation stops after this many tokens, so it also limits the
+	/// time of every try. Default is 50.
+	pub max_tokens: usize,
+	/// Minimum length in characters. Default is 0.
+	pub min_chars: usize,
+	/// Maximum length in characters. Default is [`usize::MAX`].
+	pub max_chars: usize,
+	/// Number of sentences that are generated before giving up. Default is 10.
+	pub tries: usize,
+}
+
+impl Default for SentenceOptions {
+	#[inline]
+	fn default() -> Self {
+		SentenceOptions {
+			min_tokens: 1,
+			max_tokens: 50,
+			min_chars: 0,
+			max_chars: usize::MAX,
+			tries: 10,
+		}
+	}
+}
+
+impl SentenceOptions {
+	/// Returns `true` if the sentence is within the bounds.
+	#[inline]
+	fn accepts(&self, tokens: usize, text: &str) -> bool {
+		let chars = text.chars().count();
+		(self.min_tokens..=self.max_tokens).contains(&tokens)
+			&& (self.min_chars..=self.max_chars).contains(&chars)
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Generates a whole sentence, from the start of a sentence to its end, that is within the
+	/// bounds of `options`. Sentences that are not within the bounds, and the ones that stop
+	/// before their end, are thrown away and generated again, up to [`SentenceOptions::tries`]
+	/// times.
+	///
+	/// Requires [`ChainConfig::sentence_boundaries`](crate::ChainConfig), so that the chain knows
+	/// where sentences start and end.
+	///
+	/// Returns [`MarkovError::InvalidConfig`] if the chain has no sentence boundaries or the
+	/// bounds are empty, [`MarkovError::EmptyModel`] if there is no state, and
+	/// [`MarkovError::NoSentence`] if none of the tries is within the bounds.
+	pub fn make_sentence(
+		&self,
+		options: &SentenceOptions,
+		rng: &mut impl RngCore,
+	) -> Result<String, MarkovError> {
+		if !self.config.sentence_boundaries {
+			return Err(MarkovError::InvalidConfig(
+				"sentences can only be made with sentence boundaries".to_string(),
+			));
+		}
+		if options.min_tokens > options.max_tokens || options.min_chars > options.max_chars
+		{
+			return Err(MarkovError::InvalidConfig(
+				"minimum length of the sentence must not be greater than its maximum length"
+					.to_string(),
+			));
+		}
+		if self.is_empty() {
+			return Err(MarkovError::EmptyModel);
+		}
+
+		for _ in 0..options.tries {
+			if let Some((tokens, text)) = self.sentence(options.max_tokens, rng) {
+				if options.accepts(tokens, &text) {
+					return Ok(text);
+				}
+			}
+		}
+
+		Err(MarkovError::NoSentence {
+			tries: options.tries,
+		})
+	}
+
+	/// Generates a sentence and returns it with its number of tokens.
+	///
+	/// Returns `None` if the generation stops before the end of the sentence, or if the sentence
+	/// is longer than `max_tokens`.
+	fn sentence(&self, max_tokens: usize, rng: &mut impl RngCore) -> Option<(usize, String)> {
+		let mut prev = self.start_context(None::<&str>);
+		let mut text = String::new();
+		let mut tokens = 0;
+		loop {
+			let (item, _) = self.step_source(prev.as_slice(), rng)?;
+			let next = item.get_rand(rng)?;
+			if self.is_sentence_end(next) {
+				break;
+			}
+
+			tokens += 1;
+			if tokens > max_tokens {
+				return None;
+			}
+			if !text.is_empty() {
+				text.push(' ');
+			}
+			text.push_str(self.resolve_surface(next));
+			prev.push(next);
+		}
+
+		Some((tokens, text))
+	}
+}
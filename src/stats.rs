@@ -0,0 +1,176 @@
+use std::{collections::BTreeMap, hash::BuildHasher, mem::size_of};
+
+use lasso::Spur;
+
+use crate::RawMarkovChain;
+
+#[cfg(feature = "serialize")]
+use serde::{Deserialize, Serialize};
+
+/// Approximate breakdown of the memory used by a [`RawMarkovChain`].
+///
+/// All sizes are in bytes and ignore the overhead of the allocator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+	/// Bytes allocated by the hash table of the states. Includes the inline part of every key.
+	pub table: usize,
+	/// Number of states that are stored inline, which are those with sizes lesser than or equal to `N`.
+	pub inline_keys: usize,
+	/// Number of states that are too big to be stored inline.
+	pub spilled_keys: usize,
+	/// Heap bytes used by the states that are too big to be stored inline.
+	pub spilled_key_bytes: usize,
+	/// Heap bytes used by the successor lists and their tags.
+	pub successors: usize,
+	/// Bytes used by the interners of the tokens and the tags, including the strings themselves and
+	/// the original forms of the folded tokens. A shared interner is counted in full by every chain
+	/// that uses it.
+	pub interner: usize,
+	/// Number of states of each order. Index `i` holds the number of states of size `i + 1`.
+	pub states_per_order: Vec<usize>,
+}
+
+impl MemoryUsage {
+	/// Returns the total number of bytes.
+	#[inline]
+	pub fn total(&self) -> usize {
+		self.table + self.spilled_key_bytes + self.successors + self.interner
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns an approximate breakdown of the memory used by the chain.
+	///
+	/// Iterates over every state, so it is not cheap for big chains.
+	pub fn memory_usage(&self) -> MemoryUsage {
+		let mut res = MemoryUsage {
+			table: self.items.allocation_size(),
+			states_per_order: vec![0; self.state_size],
+			..Default::default()
+		};
+
+		for (k, v) in &self.items {
+			if k.spilled() {
+				res.spilled_keys += 1;
+				res.spilled_key_bytes += k.capacity() * size_of::<Spur>();
+			} else {
+				res.inline_keys += 1;
+			}
+			res.successors += v.items.capacity() * size_of::<Spur>()
+				+ v.tags.capacity() * size_of::<Option<Spur>>();
+
+			if let Some(c) = res.states_per_order.get_mut(k.len().wrapping_sub(1)) {
+				*c += 1;
+			}
+		}
+
+		// the arenas hold the strings, the rest is the lookup table and the key to string table
+		res.interner = self.cache.memory_usage();
+		res.interner += self.tag_cache.current_memory_usage()
+			+ self.tag_cache.capacity() * (size_of::<&str>() + size_of::<Spur>());
+		// original forms of the folded tokens
+		res.interner += self.surfaces.capacity() * size_of::<Vec<(String, usize)>>()
+			+ self.surfaces
+				.iter()
+				.flatten()
+				.map(|(s, _)| size_of::<(String, usize)>() + s.capacity())
+				.sum::<usize>();
+
+		res
+	}
+}
+
+/// Statistics about the training data of a [`RawMarkovChain`]. Created by
+/// [`RawMarkovChain::corpus_stats()`].
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CorpusStats {
+	/// Number of distinct tokens that are seen in the training data.
+	pub vocabulary: usize,
+	/// Statistics of each order. Index `i` holds the statistics of the states of size `i + 1`.
+	pub orders: Vec<OrderStats>,
+}
+
+/// Statistics about the states of a single size.
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderStats {
+	/// Size of the states.
+	pub order: usize,
+	/// Number of states.
+	pub states: usize,
+	/// Number of transitions from the states, which is the number of times they are seen.
+	pub transitions: usize,
+	/// Pairs of branching factor and the number of states with it, ordered by the branching factor.
+	/// Branching factor is the number of distinct successors of a state.
+	pub branching: Vec<(usize, usize)>,
+	/// Mean branching factor of the states.
+	pub mean_branching: f64,
+	/// Entropy of the next token given the state, in bits. States are weighted by the number of
+	/// times they are seen.
+	pub conditional_entropy: f64,
+	/// Share of the states that have a single successor, between 0 and 1. States like these copy
+	/// the training data during generation.
+	pub deterministic: f64,
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Computes statistics about the training data, for each state size.
+	///
+	/// Orders whose states are mostly deterministic, or have low entropies, mostly repeat the
+	/// training data.
+	///
+	/// Iterates over every state, so it is not cheap for big chains.
+	pub fn corpus_stats(&self) -> CorpusStats {
+		let mut orders: Vec<_> = (1..=self.state_size)
+			.map(|order| OrderStats {
+				order,
+				..Default::default()
+			})
+			.collect();
+		let mut branching = vec![BTreeMap::<usize, usize>::new(); self.state_size];
+
+		for (k, v) in &self.items {
+			let Some(stats) = orders.get_mut(k.len().wrapping_sub(1)) else {
+				continue;
+			};
+
+			let counts = v.counts();
+			let total = v.items.len();
+
+			stats.states += 1;
+			stats.transitions += total;
+			*branching[k.len() - 1].entry(counts.len()).or_default() += 1;
+
+			let entropy: f64 = counts
+				.iter()
+				.map(|&(_, c)| {
+					let p = c as f64 / total as f64;
+					-p * p.log2()
+				})
+				.sum();
+			// weighted by the number of transitions, divided by the total at the end
+			stats.conditional_entropy += entropy * total as f64;
+		}
+
+		for (stats, branching) in orders.iter_mut().zip(branching) {
+			if stats.states == 0 {
+				continue;
+			}
+
+			let states = stats.states as f64;
+			let branches: usize = branching.iter().map(|(b, c)| b * c).sum();
+
+			stats.mean_branching = branches as f64 / states;
+			stats.deterministic =
+				branching.get(&1).copied().unwrap_or(0) as f64 / states;
+			stats.conditional_entropy /= stats.transitions as f64;
+			stats.branching = branching.into_iter().collect();
+		}
+
+		CorpusStats {
+			vocabulary: self.vocabulary().count(),
+			orders,
+		}
+	}
+}
@@ -0,0 +1,322 @@
+use std::hash::BuildHasher;
+
+use foldhash::fast::FixedState;
+use lasso::{Key, Spur};
+use rand::{Rng, RngCore};
+
+use crate::{
+	chain::{join_tokens, Steps},
+	ChainItem, FallbackPolicy, RawMarkovChain,
+};
+
+/// Weights of the tags, which are used for generating from the tagged training data of a
+/// [`RawMarkovChain`].
+///
+/// Every transition is picked in proportion to the number of times it is seen with a tag, times the
+/// weight of the tag. Tags without weights, and the data that is added without a tag, have the
+/// weight of 0 unless set otherwise.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TagWeights {
+	weights: Vec<(String, f64)>,
+	untagged: f64,
+}
+
+impl TagWeights {
+	/// Creates an empty set of weights, which doesn't allow any transition.
+	#[inline]
+	pub fn new() -> TagWeights {
+		TagWeights::default()
+	}
+
+	/// Creates weights that only allow the given tags, with the same weight.
+	pub fn only<T>(tags: T) -> TagWeights
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		tags.into_iter()
+			.fold(TagWeights::new(), |w, t| w.with(t.as_ref(), 1.0))
+	}
+
+	/// Sets the weight of the tag. Weights that are not positive, or not finite, disable the tag.
+	#[inline]
+	pub fn with(mut self, tag: &str, weight: f64) -> TagWeights {
+		match self.weights.iter_mut().find(|(t, _)| t == tag) {
+			Some((_, w)) => *w = weight,
+			None => self.weights.push((tag.to_string(), weight)),
+		}
+
+		self
+	}
+
+	/// Sets the weight of the data that is added without a tag. Weights that are not positive, or
+	/// not finite, disable it.
+	#[inline]
+	pub fn with_untagged(mut self, weight: f64) -> TagWeights {
+		self.untagged = weight;
+		self
+	}
+}
+
+/// [`TagWeights`] that are resolved for a chain.
+#[derive(Clone, Debug)]
+pub(crate) struct ResolvedTags {
+	/// Weights indexed by the keys of the tags.
+	weights: Vec<f64>,
+	untagged: f64,
+}
+
+impl ResolvedTags {
+	/// Returns the weight of the tag, which is 0 if it is not positive or not finite.
+	#[inline]
+	fn weight(&self, tag: Option<Spur>) -> f64 {
+		let w = match tag {
+			Some(t) => self.weights.get(t.into_usize()).copied().unwrap_or(0.0),
+			None => self.untagged,
+		};
+
+		if w > 0.0 && w.is_finite() {
+			w
+		} else {
+			0.0
+		}
+	}
+
+	/// Scales the weights down to at most 1, so that their sum can't overflow.
+	fn normalize(&mut self) {
+		let max = self
+			.weights
+			.iter()
+			.chain([&self.untagged])
+			.copied()
+			.filter(|w| w.is_finite())
+			.fold(0.0, f64::max);
+		if max > 0.0 {
+			self.weights.iter_mut().for_each(|w| *w /= max);
+			self.untagged /= max;
+		}
+	}
+}
+
+impl ChainItem {
+	/// Gets a random item, in proportion to the weights of their tags.
+	///
+	/// Returns `None` if none of the items has a positive weight.
+	fn get_rand_tagged(&self, weights: &ResolvedTags, rng: &mut impl RngCore) -> Option<Spur> {
+		if self.tags.is_empty() {
+			return if weights.weight(None) > 0.0 {
+				self.get_rand(rng)
+			} else {
+				None
+			};
+		}
+
+		let total: f64 = self.tags.iter().map(|&t| weights.weight(t)).sum();
+		if total <= 0.0 {
+			return None;
+		}
+
+		let mut r = rng.gen_range(0.0..total);
+		let mut res = None;
+		for (&s, &t) in self.items.iter().zip(&self.tags) {
+			let w = weights.weight(t);
+			if w <= 0.0 {
+				continue;
+			}
+
+			// the last allowed item is picked if the sum is off due to rounding
+			res = Some(s);
+			if r < w {
+				break;
+			}
+			r -= w;
+		}
+
+		res
+	}
+
+	/// Returns `true` if any of the items has a positive weight.
+	#[inline]
+	fn has_tagged(&self, weights: &ResolvedTags) -> bool {
+		if self.tags.is_empty() {
+			weights.weight(None) > 0.0
+		} else {
+			self.tags.iter().any(|&t| weights.weight(t) > 0.0)
+		}
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Adds text as training data with the given tag, like a genre or a source. Tagged data can be
+	/// used for generating text that is similar to the data of some of the tags, see
+	/// [`TagWeights`].
+	///
+	/// Untagged generation uses the data of every tag.
+	///
+	/// Tokens are not added if they exceed the [`Limits`](crate::Limits) of the chain, see
+	/// [`RawMarkovChain::try_add_tokens_tagged()`].
+	#[inline]
+	pub fn add_tokens_tagged<T>(&mut self, tokens: T, tag: &str)
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		let _ = self.train(tokens, 1, Some(tag));
+	}
+
+	/// Returns an iterator over every tag that the chain knows, in the order they are added.
+	#[inline]
+	pub fn tags(&self) -> impl Iterator<Item = &str> {
+		self.tag_cache.strings()
+	}
+
+	/// Does the same thing as [`RawMarkovChain::generate()`], but only uses the transitions that
+	/// are allowed by `weights`, in proportion to them.
+	///
+	/// Returns `None` if there is no state.
+	pub fn generate_tagged(
+		&self,
+		length: usize,
+		weights: &TagWeights,
+		rng: &mut impl RngCore,
+	) -> Option<String> {
+		self.generate_start_tagged(None::<&str>, length, weights, rng)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::generate_start()`], but only uses the transitions
+	/// that are allowed by `weights`, in proportion to them.
+	///
+	/// Returns `None` if there is no state.
+	pub fn generate_start_tagged<T>(
+		&self,
+		start: T,
+		length: usize,
+		weights: &TagWeights,
+		rng: &mut impl RngCore,
+	) -> Option<String>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+	{
+		if self.is_empty() {
+			return None;
+		}
+
+		Some(join_tokens(
+			self.iter_start_tagged(start, length, weights, rng),
+		))
+	}
+
+	/// Does the same thing as [`RawMarkovChain::generate_tagged()`] but instead of returning a
+	/// String, returns a lazily evaluated iterator.
+	#[inline]
+	pub fn iter_tagged<R: RngCore>(
+		&self,
+		count: usize,
+		weights: &TagWeights,
+		rng: R,
+	) -> TaggedMarkovChainIter<'_, N, R, H> {
+		self.iter_start_tagged(None::<&str>, count, weights, rng)
+	}
+
+	/// Does the same thing as [`RawMarkovChain::generate_start_tagged()`] but instead of returning
+	/// a String, returns a lazily evaluated iterator.
+	pub fn iter_start_tagged<T, R>(
+		&self,
+		start: T,
+		count: usize,
+		weights: &TagWeights,
+		rng: R,
+	) -> TaggedMarkovChainIter<'_, N, R, H>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		let mut resolved = ResolvedTags {
+			weights: vec![0.0; self.tag_cache.len()],
+			untagged: weights.untagged,
+		};
+		for (t, w) in &weights.weights {
+			if let Some(t) = self.tag_cache.get(t) {
+				resolved.weights[t.into_usize()] = *w;
+			}
+		}
+		resolved.normalize();
+
+		TaggedMarkovChainIter {
+			chain: self,
+			steps: Steps::new(count, rng, self.start_context(start)),
+			weights: resolved,
+			fallback: None,
+		}
+	}
+
+	/// Returns the appropriate next step for the given previous state, from the transitions that
+	/// are allowed by `weights`.
+	///
+	/// `fallback` holds the states that have allowed transitions, which are collected on the first
+	/// fallback and reused by the next ones.
+	///
+	/// Returns `None` if there is no such transition, or if the generation stops like
+	/// [`RawMarkovChain::next_step()`].
+	fn next_step_tagged<'a>(
+		&'a self,
+		prev: &[Spur],
+		weights: &ResolvedTags,
+		fallback: &mut Option<Vec<&'a ChainItem>>,
+		rng: &mut impl RngCore,
+	) -> Option<Spur> {
+		for i in 0..prev.len() {
+			if let Some(res) = self
+				.items
+				.get(&prev[i..])
+				.and_then(|item| item.get_rand_tagged(weights, rng))
+			{
+				return Some(res).filter(|&s| !self.is_sentence_end(s));
+			}
+		}
+
+		if !prev.is_empty() && self.config.fallback == FallbackPolicy::Stop {
+			return None;
+		}
+
+		// like RawMarkovChain::fallback, but only from the states that have allowed transitions
+		let states = fallback.get_or_insert_with(|| {
+			let mut states: Vec<_> = self
+				.items
+				.iter()
+				.filter(|(_, item)| item.has_tagged(weights))
+				.collect();
+			states.sort_unstable_by(|a, b| self.cmp_states(a.0, b.0));
+			states.into_iter().map(|(_, item)| item).collect()
+		});
+		if states.is_empty() {
+			return None;
+		}
+
+		states[rng.gen_range(0..states.len())]
+			.get_rand_tagged(weights, rng)
+			.filter(|&s| !self.is_sentence_end(s))
+	}
+}
+
+/// Iterator that iterates over generation steps from tagged training data. Created by
+/// [`RawMarkovChain::iter_tagged()`].
+pub struct TaggedMarkovChainIter<'a, const N: usize, R, H = FixedState> {
+	chain: &'a RawMarkovChain<N, H>,
+	steps: Steps<N, R>,
+	weights: ResolvedTags,
+	fallback: Option<Vec<&'a ChainItem>>,
+}
+
+impl<'a, const N: usize, R: RngCore, H: BuildHasher> Iterator
+	for TaggedMarkovChainIter<'a, N, R, H>
+{
+	type Item = &'a str;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		let (chain, weights, fallback) = (self.chain, &self.weights, &mut self.fallback);
+		self.steps.step(|prev, rng| {
+			let next = chain.next_step_tagged(prev, weights, fallback, rng)?;
+			Some((next, chain.resolve_surface(next)))
+		})
+	}
+}
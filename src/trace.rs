@@ -0,0 +1,104 @@
+use std::hash::BuildHasher;
+
+use foldhash::fast::FixedState;
+use rand::RngCore;
+
+use crate::{chain::Steps, RawMarkovChain};
+
+/// A generated token with the details of how it is picked. Created by
+/// [`RawMarkovChain::iter_traced()`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TracedStep<'a> {
+	/// Generated token.
+	pub token: &'a str,
+	/// Previous tokens that the token is picked by, from the oldest to the newest. Its length is
+	/// the size of the longest state that matches the end of the previous tokens, and it is empty
+	/// if the chain fell back to a random state.
+	pub context: Vec<&'a str>,
+	/// Probability of the token in the state that it is picked from.
+	pub probability: f64,
+	/// Number of distinct tokens that could be picked from the state.
+	pub candidates: usize,
+	/// `true` if none of the previous tokens is known, and the token is picked from a random
+	/// state.
+	pub fallback: bool,
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Does the same thing as [`RawMarkovChain::iter()`], but yields the details of how every
+	/// token is picked, for debugging the chain.
+	///
+	/// Tokens are the same as the ones of [`RawMarkovChain::iter()`] for the same RNG.
+	#[inline]
+	pub fn iter_traced<R: RngCore>(
+		&self,
+		count: usize,
+		rng: R,
+	) -> TracedMarkovChainIter<'_, N, R, H> {
+		TracedMarkovChainIter {
+			chain: self,
+			steps: Steps::new(count, rng, self.start_context(None::<&str>)),
+		}
+	}
+
+	/// Does the same thing as [`RawMarkovChain::iter_start()`], but yields the details of how
+	/// every token is picked, like [`RawMarkovChain::iter_traced()`].
+	#[inline]
+	pub fn iter_start_traced<T, R>(
+		&self,
+		start: T,
+		count: usize,
+		rng: R,
+	) -> TracedMarkovChainIter<'_, N, R, H>
+	where
+		T: IntoIterator<Item: AsRef<str>>,
+		R: RngCore,
+	{
+		TracedMarkovChainIter {
+			chain: self,
+			steps: Steps::new(count, rng, self.start_context(start)),
+		}
+	}
+}
+
+/// Iterator that iterates over generation steps with their details. Created by
+/// [`RawMarkovChain::iter_traced()`].
+///
+/// Generic over the RNG, which can be either borrowed, like `&mut ThreadRng`, or owned.
+pub struct TracedMarkovChainIter<'a, const N: usize, R, H = FixedState> {
+	chain: &'a RawMarkovChain<N, H>,
+	steps: Steps<N, R>,
+}
+
+impl<'a, const N: usize, R: RngCore, H: BuildHasher> Iterator
+	for TracedMarkovChainIter<'a, N, R, H>
+{
+	type Item = TracedStep<'a>;
+
+	#[inline]
+	fn next(&mut self) -> Option<Self::Item> {
+		let chain = self.chain;
+		self.steps.step(|prev, rng| {
+			let (item, matched) = chain.step_source(prev, rng)?;
+			let next = item.get_rand(rng).filter(|&s| !chain.is_sentence_end(s))?;
+
+			let counts = item.counts();
+			let count = counts
+				.iter()
+				.find(|(s, _)| *s == next)
+				.map_or(0, |(_, c)| *c);
+			let step = TracedStep {
+				token: chain.resolve_surface(next),
+				context: prev[(prev.len() - matched)..]
+					.iter()
+					.map(|&s| chain.resolve_surface(s))
+					.collect(),
+				probability: count as f64 / item.items.len() as f64,
+				candidates: counts.len(),
+				fallback: matched == 0,
+			};
+
+			Some((next, step))
+		})
+	}
+}
@@ -0,0 +1,175 @@
+use std::hash::BuildHasher;
+
+use lasso::{Key, Spur};
+
+use crate::{MarkovError, RawMarkovChain, MAX_STATE_SIZE};
+
+#[cfg(feature = "serialize")]
+use {
+	crate::{interner::Interner, normalize::SurfaceForms, ChainConfig, ChainItem},
+	hashbrown::HashMap,
+	lasso::Rodeo,
+	serde::Deserialize,
+	smallvec::SmallVec,
+};
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Checks that the data of the chain is consistent, so that generating from it can't panic or
+	/// stop unexpectedly.
+	///
+	/// Chains are checked while they are deserialized, so this is only needed for the chains that
+	/// are built in other ways.
+	///
+	/// Returns [`MarkovError::Integrity`] if the state size is greater than [`MAX_STATE_SIZE`], or
+	/// if a state is empty or longer than the state size, has no successor, or refers to a token or
+	/// a tag that is not interned.
+	pub fn validate(&self) -> Result<(), MarkovError> {
+		if self.state_size > MAX_STATE_SIZE {
+			return Err(integrity(format!(
+				"state size is {}, but it must not be greater than {}",
+				self.state_size, MAX_STATE_SIZE
+			)));
+		}
+
+		let config = &self.config;
+		if self.state_size > 0
+			&& (config.min_order == 0 || config.min_order > self.state_size)
+		{
+			return Err(integrity(format!(
+				"minimum order is {}, but the state size is {}",
+				config.min_order, self.state_size
+			)));
+		}
+
+		for (k, v) in &self.items {
+			if k.is_empty() || k.len() > self.state_size {
+				return Err(integrity(format!(
+					"state {:?} has {} tokens, but the state size is {}",
+					keys(k),
+					k.len(),
+					self.state_size
+				)));
+			}
+			if let Some(s) = k.iter().find(|s| !is_interned(self.cache.len(), **s)) {
+				return Err(integrity(format!(
+					"state {:?} refers to token {}, but there are {} tokens",
+					keys(k),
+					s.into_usize(),
+					self.cache.len()
+				)));
+			}
+
+			if v.items.is_empty() {
+				return Err(integrity(format!(
+					"state {:?} has no successor",
+					keys(k)
+				)));
+			}
+			if let Some(s) =
+				v.items.iter().find(|s| !is_interned(self.cache.len(), **s))
+			{
+				return Err(integrity(format!(
+					"a successor of state {:?} refers to token {}, but there are {} tokens",
+					keys(k),
+					s.into_usize(),
+					self.cache.len()
+				)));
+			}
+
+			if !v.tags.is_empty() && v.tags.len() != v.items.len() {
+				return Err(integrity(format!(
+					"state {:?} has {} successors, but {} tags",
+					keys(k),
+					v.items.len(),
+					v.tags.len()
+				)));
+			}
+			if let Some(t) =
+				v.tags.iter()
+					.flatten()
+					.find(|t| !is_interned(self.tag_cache.len(), **t))
+			{
+				return Err(integrity(format!(
+					"a successor of state {:?} refers to tag {}, but there are {} tags",
+					keys(k),
+					t.into_usize(),
+					self.tag_cache.len()
+				)));
+			}
+		}
+
+		if self.token_counts.len() > self.cache.len() {
+			return Err(integrity(format!(
+				"there are counts of {} tokens, but there are {} tokens",
+				self.token_counts.len(),
+				self.cache.len()
+			)));
+		}
+		if self.surfaces.len() > self.cache.len() {
+			return Err(integrity(format!(
+				"there are original forms of {} tokens, but there are {} tokens",
+				self.surfaces.len(),
+				self.cache.len()
+			)));
+		}
+
+		Ok(())
+	}
+}
+
+#[inline]
+fn is_interned(len: usize, s: Spur) -> bool {
+	s.into_usize() < len
+}
+
+/// Returns the indices of the tokens of the state, for the error messages.
+#[inline]
+fn keys(key: &[Spur]) -> Vec<usize> {
+	key.iter().map(|s| s.into_usize()).collect()
+}
+
+#[inline]
+fn integrity(message: String) -> MarkovError {
+	MarkovError::Integrity(message)
+}
+
+/// Layout of a serialized [`RawMarkovChain`], which is validated before it is converted into one.
+#[cfg(feature = "serialize")]
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "H: BuildHasher + Default"))]
+pub(crate) struct UnvalidatedChain<const N: usize, H> {
+	#[serde(with = "crate::entries")]
+	items: HashMap<SmallVec<[Spur; N]>, ChainItem, H>,
+	state_size: usize,
+	cache: Interner,
+	#[serde(default)]
+	token_counts: Vec<usize>,
+	#[serde(default)]
+	config: ChainConfig,
+	#[serde(default)]
+	surfaces: Vec<SurfaceForms>,
+	#[serde(default)]
+	tag_cache: Rodeo,
+}
+
+#[cfg(feature = "serialize")]
+impl<const N: usize, H: BuildHasher> TryFrom<UnvalidatedChain<N, H>> for RawMarkovChain<N, H> {
+	type Error = MarkovError;
+
+	fn try_from(data: UnvalidatedChain<N, H>) -> Result<Self, Self::Error> {
+		let mut chain = RawMarkovChain {
+			items: data.items,
+			state_size: data.state_size,
+			cache: data.cache,
+			token_counts: data.token_counts,
+			config: data.config,
+			surfaces: data.surfaces,
+			tag_cache: data.tag_cache,
+			memory: 0,
+		};
+		chain.validate()?;
+		chain.measure_memory();
+
+		Ok(chain)
+	}
+}
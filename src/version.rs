@@ -0,0 +1,265 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt,
+	hash::BuildHasher,
+	marker::PhantomData,
+};
+
+use foldhash::fast::FixedState;
+use lasso::{Key, Rodeo, Spur};
+use serde::{
+	de::{self, value::U32Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor},
+	ser::SerializeStruct,
+	Deserialize, Deserializer, Serialize, Serializer,
+};
+use smallvec::SmallVec;
+
+use crate::{interner::Interner, ChainItem, RawMarkovChain};
+
+/// Version of the format that is written by [`RawMarkovChain::versioned()`].
+///
+/// | Version | Released in | Layout |
+/// | --- | --- | --- |
+/// | none | 0.2, 0.3 | States are a map with stringified keys, some chains have a regex. |
+/// | 1 | after 0.3 | States are a sequence of entries, chains have a configuration. |
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Serializes a [`RawMarkovChain`] with the version of its format. Created by
+/// [`RawMarkovChain::versioned()`].
+pub struct Versioned<'a, const N: usize, H = FixedState>(&'a RawMarkovChain<N, H>);
+
+impl<const N: usize, H: BuildHasher> Serialize for Versioned<'_, N, H> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut state = serializer.serialize_struct("Versioned", 2)?;
+		state.serialize_field("version", &FORMAT_VERSION)?;
+		state.serialize_field("chain", self.0)?;
+		state.end()
+	}
+}
+
+impl<const N: usize, H: BuildHasher> RawMarkovChain<N, H> {
+	/// Returns a wrapper that serializes the chain with the version of its format, so that the
+	/// later versions of the crate can read it with [`RawMarkovChain::deserialize_versioned()`].
+	#[inline]
+	pub fn versioned(&self) -> Versioned<'_, N, H> {
+		Versioned(self)
+	}
+}
+
+impl<const N: usize, H: BuildHasher + Default> RawMarkovChain<N, H> {
+	/// Deserializes a chain that is serialized by [`RawMarkovChain::versioned()`], migrating it
+	/// from the format of an earlier version if needed.
+	///
+	/// With self-describing formats like JSON, chains that are serialized by the releases without a
+	/// version, 0.2 and 0.3, are read as well. Use [`RawMarkovChain::deserialize_legacy()`] to read
+	/// those with the other formats.
+	///
+	/// Returns an error if the version is newer than [`FORMAT_VERSION`].
+	#[inline]
+	pub fn deserialize_versioned<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<RawMarkovChain<N, H>, D::Error> {
+		deserializer.deserialize_struct(
+			"Versioned",
+			&["version", "chain"],
+			VersionedVisitor(PhantomData),
+		)
+	}
+
+	/// Deserializes a chain that is serialized by the releases without a version, 0.2 and 0.3.
+	///
+	/// Regexes of these chains are ignored.
+	#[inline]
+	pub fn deserialize_legacy<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<RawMarkovChain<N, H>, D::Error> {
+		let legacy = LegacyChain::deserialize(deserializer)?;
+		legacy.migrate()
+	}
+}
+
+/// Fields of the versioned format and of the formats without a version.
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum Field {
+	Version,
+	Chain,
+	Items,
+	StateSize,
+	Regex,
+	Cache,
+	#[serde(other)]
+	Other,
+}
+
+struct VersionedVisitor<const N: usize, H>(PhantomData<H>);
+
+impl<'de, const N: usize, H: BuildHasher + Default> Visitor<'de> for VersionedVisitor<N, H> {
+	type Value = RawMarkovChain<N, H>;
+
+	fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "a versioned chain")
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+		let version: u32 = seq
+			.next_element()?
+			.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+		check_version::<A::Error>(version)?;
+
+		seq.next_element()?
+			.ok_or_else(|| de::Error::invalid_length(1, &self))
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+		let mut version = None;
+		let mut chain = None;
+		let mut legacy = LegacyChain::default();
+		let mut is_legacy = false;
+
+		while let Some(field) = map.next_key()? {
+			match field {
+				Field::Version => {
+					let v: u32 = map.next_value()?;
+					check_version::<A::Error>(v)?;
+					version = Some(v);
+				}
+				// the version decides how the chain is read, so it has to come first
+				Field::Chain if version.is_none() => {
+					return Err(de::Error::custom(
+						"version must come before the chain",
+					));
+				}
+				Field::Chain => chain = Some(map.next_value()?),
+				Field::Items => {
+					is_legacy = true;
+					legacy.items = map.next_value()?;
+				}
+				Field::StateSize => {
+					is_legacy = true;
+					legacy.state_size = map.next_value()?;
+				}
+				Field::Cache => {
+					is_legacy = true;
+					legacy.cache = map.next_value()?;
+				}
+				Field::Regex | Field::Other => {
+					let _: IgnoredAny = map.next_value()?;
+				}
+			}
+		}
+
+		match chain {
+			Some(chain) => Ok(chain),
+			None if is_legacy => legacy.migrate(),
+			None => Err(de::Error::missing_field("chain")),
+		}
+	}
+}
+
+/// Returns an error if the version can't be read.
+#[inline]
+fn check_version<E: de::Error>(version: u32) -> Result<(), E> {
+	if version == 0 || version > FORMAT_VERSION {
+		return Err(E::custom(format!(
+			"unsupported format version {}, the newest supported version is {}",
+			version, FORMAT_VERSION
+		)));
+	}
+
+	Ok(())
+}
+
+/// Layout of the chains of 0.2 and 0.3. States are a map with stringified keys, like `"[1,2]"`.
+#[derive(Default, Deserialize)]
+struct LegacyChain {
+	items: BTreeMap<String, Vec<Spur>>,
+	state_size: usize,
+	// not every version writes the regex
+	#[serde(default, rename = "regex")]
+	_regex: IgnoredAny,
+	cache: Rodeo,
+}
+
+impl LegacyChain {
+	/// Converts the chain into the current layout.
+	fn migrate<const N: usize, H, E>(self) -> Result<RawMarkovChain<N, H>, E>
+	where
+		H: BuildHasher + Default,
+		E: de::Error,
+	{
+		let mut chain = RawMarkovChain::with_capacity_and_hasher(
+			self.state_size,
+			self.items.len(),
+			H::default(),
+		);
+		chain.cache = Interner::Owned(self.cache);
+
+		for (k, items) in self.items {
+			let key = parse_legacy_key::<N, E>(&k)?;
+			chain.items.insert(
+				key,
+				ChainItem {
+					items,
+					tags: Vec::new(),
+				},
+			);
+		}
+		chain.validate().map_err(E::custom)?;
+		chain.token_counts = legacy_counts(&chain);
+		chain.measure_memory();
+
+		Ok(chain)
+	}
+}
+
+/// Counts the tokens of a legacy chain, which doesn't store them.
+///
+/// Legacy chains only have the transitions to the tokens after the first state of a text, so every
+/// token is counted when it follows one of the longest states, and the tokens of the first states
+/// are counted as well. A state is first as many times as it is not preceded by another one.
+fn legacy_counts<const N: usize, H>(chain: &RawMarkovChain<N, H>) -> Vec<usize> {
+	let mut counts = vec![0usize; chain.cache.len()];
+	let mut firsts: HashMap<SmallVec<[Spur; N]>, isize> = HashMap::new();
+	for (key, item) in chain
+		.items
+		.iter()
+		.filter(|(k, _)| k.len() == chain.state_size)
+	{
+		*firsts.entry(key.clone()).or_default() += item.items.len() as isize;
+		for &s in &item.items {
+			counts[s.into_usize()] += 1;
+
+			let next = key[1..].iter().copied().chain([s]).collect();
+			*firsts.entry(next).or_default() -= 1;
+		}
+	}
+	for (key, n) in firsts.into_iter().filter(|&(_, n)| n > 0) {
+		for s in key {
+			counts[s.into_usize()] += n as usize;
+		}
+	}
+
+	// every token of the interner is in the text at least once
+	counts.iter_mut().for_each(|c| *c = (*c).max(1));
+	counts
+}
+
+/// Parses a stringified key, like `"[1,2]"`.
+fn parse_legacy_key<const N: usize, E: de::Error>(key: &str) -> Result<SmallVec<[Spur; N]>, E> {
+	let invalid = || E::invalid_value(de::Unexpected::Str(key), &"a stringified list of keys");
+
+	let inner = key
+		.trim()
+		.strip_prefix('[')
+		.and_then(|k| k.strip_suffix(']'))
+		.ok_or_else(invalid)?;
+
+	inner.split(',')
+		.map(|n| {
+			let n: u32 = n.trim().parse().map_err(|_| invalid())?;
+			// keys are read like they are serialized, so their representation stays with lasso
+			Spur::deserialize(U32Deserializer::<E>::new(n))
+		})
+		.collect()
+}
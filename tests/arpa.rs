@@ -0,0 +1,136 @@
+use markov_str::*;
+use rand::SeedableRng;
+
+#[test]
+fn export() {
+	let mut chain = MarkovChain::new(1);
+	chain.add_tokens("a b a c a b".split(' '));
+
+	let mut out = Vec::new();
+	chain.write_arpa(&mut out).unwrap();
+
+	assert_eq!(
+		String::from_utf8(out).unwrap(),
+		format!(
+			"\\data\\
+ngram 1=3
+ngram 2=4
+
+\\1-grams:
+{}\ta\t-99
+{}\tb\t-99
+{}\tc\t-99
+
+\\2-grams:
+{}\ta b
+{}\ta c
+0\tb a
+0\tc a
+
+\\end\\
+",
+			0.5f64.log10(),
+			(2.0f64 / 6.0).log10(),
+			(1.0f64 / 6.0).log10(),
+			(2.0f64 / 3.0).log10(),
+			(1.0f64 / 3.0).log10(),
+		)
+	);
+}
+
+#[test]
+fn round_trip() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens("the cat sat on the mat and the cat ate the rat".split(' '));
+	chain.add_tokens_weighted("the dog sat on the cat".split(' '), 3);
+
+	let mut out = Vec::new();
+	chain.write_arpa(&mut out).unwrap();
+	let imported = MarkovChain::from_arpa(out.as_slice()).unwrap();
+
+	assert_eq!(imported.state_size(), 2);
+	assert_eq!(imported.len(), chain.len());
+	for state in chain.states() {
+		let mut expected = state.successors();
+		let mut actual = imported.successors(state.tokens()).unwrap();
+		expected.sort();
+		actual.sort();
+
+		// counts with a common divisor are read back divided
+		let gcd = expected.iter().fold(0, |a, &(_, c)| gcd(a, c));
+		expected.iter_mut().for_each(|(_, c)| *c /= gcd);
+		assert_eq!(actual, expected);
+	}
+	for token in chain.vocabulary() {
+		assert_eq!(imported.token_count(token), chain.token_count(token));
+	}
+}
+
+#[test]
+fn sentence_markers() {
+	let mut chain: MarkovChain = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.build()
+		.unwrap();
+	chain.add_text("The dog ate the bone. The cat ate the fish.");
+
+	let mut out = Vec::new();
+	chain.write_arpa(&mut out).unwrap();
+	let out = String::from_utf8(out).unwrap();
+	assert!(out.contains("\t<s> <s> The\n"));
+	assert!(out.contains("\tbone. </s>\n"));
+	assert!(!out.contains(SENTENCE_START) && !out.contains(SENTENCE_END));
+
+	let imported = MarkovChain::from_arpa(out.as_bytes()).unwrap();
+	assert!(imported.config().sentence_boundaries);
+	assert_eq!(imported.len(), chain.len());
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let sentence = imported
+		.make_sentence(&SentenceOptions::default(), &mut rng)
+		.unwrap();
+	assert!(sentence.ends_with('.'));
+}
+
+#[test]
+fn extreme_probabilities() {
+	// weights are capped, so the successors can't take too much memory
+	for logp in ["-12", "-98"] {
+		let chain = MarkovChain::from_arpa(
+			format!("\\data\\\nngram 1=3\nngram 2=2\n\n\\1-grams:\n-0.5\ta\n-0.5\tb\n-0.5\tc\n\n\\2-grams:\n{}\ta b\n0\ta c\n\n\\end\\\n", logp)
+				.as_bytes(),
+		)
+		.unwrap();
+		assert_eq!(chain.successors(["a"]).unwrap(), [("b", 1), ("c", 1000)]);
+	}
+}
+
+#[test]
+fn invalid() {
+	let res = MarkovChain::from_arpa(
+		"\\data\\\nngram 1=2\n\n\\1-grams:\n-0.3\ta\n\n\\end\\\n".as_bytes(),
+	);
+	assert!(matches!(res, Err(ArpaError::Parse { line: 7, .. })));
+
+	let res = MarkovChain::from_arpa(
+		"\\data\\\nngram 1=1\n\n\\1-grams:\nfoo\ta\n\\end\\\n".as_bytes(),
+	);
+	assert!(matches!(res, Err(ArpaError::Parse { line: 5, .. })));
+
+	let res = MarkovChain::from_arpa("".as_bytes());
+	assert!(matches!(res, Err(ArpaError::Parse { .. })));
+
+	// counts of the header are not trusted
+	let res = MarkovChain::from_arpa(
+		"\\data\\\nngram 1=18446744073709551615\n\n\\1-grams:\n-0.3\ta\n\n\\end\\\n"
+			.as_bytes(),
+	);
+	assert!(matches!(res, Err(ArpaError::Parse { line: 7, .. })));
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+	if b == 0 {
+		a
+	} else {
+		gcd(b, a % b)
+	}
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "rayon")]
+
+use markov_str::*;
+
+const TEST_TEXT: &str = "Hey guys, did you know that Vaporeon can learn Mist in Yellow, but only under a very specific circumstance? In Yellow, Vaporeon is meant to learn both Haze and Mist at level 42.";
+
+#[test]
+fn batch() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEST_TEXT.split(' '));
+
+	let res = chain.generate_batch(100, 10, 1337).unwrap();
+
+	assert_eq!(res.len(), 100);
+	assert!(res.iter().all(|s| s.split(' ').count() == 10));
+	assert_eq!(chain.generate_batch(100, 10, 1337).unwrap(), res);
+	assert_ne!(chain.generate_batch(100, 10, 1338).unwrap(), res);
+}
+
+#[test]
+fn batch_threads() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEST_TEXT.split(' '));
+
+	let single = rayon::ThreadPoolBuilder::new()
+		.num_threads(1)
+		.build()
+		.unwrap()
+		.install(|| chain.generate_batch(50, 10, 42).unwrap());
+	let multi = rayon::ThreadPoolBuilder::new()
+		.num_threads(4)
+		.build()
+		.unwrap()
+		.install(|| chain.generate_batch(50, 10, 42).unwrap());
+
+	assert_eq!(single, multi);
+}
+
+#[test]
+fn batch_empty() {
+	let chain = MarkovChain::new(2);
+
+	assert_eq!(chain.generate_batch(10, 10, 1337), None);
+}
@@ -14,6 +14,45 @@ fn state_size_zero() {
 	assert_eq!(chain.generate(10, &mut rng), None)
 }
 
+#[test]
+fn errors() {
+	assert!(matches!(
+		MarkovChain::try_new(0),
+		Err(MarkovError::InvalidConfig(_))
+	));
+	assert!(MarkovChain::try_with_capacity(0, 10).is_err());
+	assert!(MarkovChain::try_new(MAX_STATE_SIZE + 1).is_err());
+	assert!(MarkovChain::try_new(MAX_STATE_SIZE).is_ok());
+
+	let mut chain = MarkovChain::try_new(2).unwrap();
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	assert_eq!(
+		chain.try_generate(10, &mut rng),
+		Err(MarkovError::EmptyModel)
+	);
+
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+	assert_eq!(
+		chain.try_generate(10, &mut rng).unwrap().split(' ').count(),
+		10
+	);
+	assert_eq!(
+		chain.try_generate_start(["Vaporeon", "Eevee"], 10, &mut rng),
+		Err(MarkovError::UnknownSeed(UnknownSeedTokens {
+			matched: 1,
+			tokens: vec!["Eevee".to_string()],
+		}))
+	);
+	assert!(chain
+		.try_generate_start(["Vaporeon", "is"], 10, &mut rng)
+		.unwrap()
+		.starts_with("meant to learn"));
+	assert_eq!(
+		MarkovError::DeadEnd { generated: 3 }.to_string(),
+		"generation reached a dead end after 3 tokens"
+	);
+}
+
 #[test]
 fn empty_str() {
 	let mut chain = MarkovChain::new(2);
@@ -78,11 +117,14 @@ fn seed1() {
 
 		assert_eq!(
 			chain.generate(10, &mut rng),
-			Some("in the Daycare it will learn both Haze and Mist.".to_string())
+			Some(
+				"under a very specific circumstance? In Yellow Vaporeon is meant"
+					.to_string()
+			)
 		);
 		assert_eq!(
 			chain.generate(10, &mut rng),
-			Some("programming at the time is so bad it's impossible for".to_string())
+			Some("moves at the time is so bad it's impossible for".to_string())
 		);
 		assert_eq!(
 			chain.generate(10, &mut rng),
@@ -302,6 +344,86 @@ fn iter_start3() {
 	}
 }
 
+#[test]
+fn iter_owned_rng() {
+	let mut chain = MarkovChain::new(3);
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let expected = chain
+		.generate_start(word_regex_matches("Vaporeon"), 100, &mut rng)
+		.unwrap();
+
+	// the iterator owns the RNG, so it can be sent to another thread
+	let rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let iter = chain.iter_start(word_regex_matches("Vaporeon"), 100, rng);
+	let res = std::thread::scope(|s| {
+		s.spawn(move || iter.collect::<Vec<&str>>().join(" "))
+			.join()
+			.unwrap()
+	});
+
+	assert_eq!(res, expected);
+}
+
+#[test]
+fn iter_shared_chain() {
+	let mut chain = MarkovChain::new(3);
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+	let chain = std::sync::Arc::new(chain);
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let expected = chain
+		.generate_start(word_regex_matches("Vaporeon"), 100, &mut rng)
+		.unwrap();
+
+	// the iterator doesn't borrow the chain, so it can be moved into a spawned thread
+	let rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let iter = chain.iter_start_owned(word_regex_matches("Vaporeon"), 100, rng);
+	let res = std::thread::spawn(move || iter.collect::<Vec<String>>().join(" "))
+		.join()
+		.unwrap();
+
+	assert_eq!(res, expected);
+}
+
+#[test]
+fn seed_strict() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEST_TEXT.split(' '));
+
+	assert_eq!(
+		chain.match_seed(["Hey", "Pikachu", "Vaporeon", "Eevee"]),
+		SeedMatch {
+			matched: 1,
+			unknown: vec!["Eevee".to_string()],
+		}
+	);
+
+	let err = chain
+		.generate_start_strict(["Vaporeon", "Eevee"], 10, &mut rand::thread_rng())
+		.unwrap_err();
+	assert_eq!(err.matched, 1);
+	assert_eq!(err.tokens, ["Eevee"]);
+	assert_eq!(err.to_string(), r#"unknown seed tokens: "Eevee""#);
+	assert!(chain
+		.iter_start_strict(["Pikachu"], 10, rand::thread_rng())
+		.is_err());
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let res = chain
+		.generate_start_strict(["Vaporeon", "is"], 10, &mut rng)
+		.unwrap();
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let expected = chain
+		.generate_start(["Vaporeon", "is"], 10, &mut rng)
+		.unwrap();
+
+	assert_eq!(res.matched, 2);
+	assert_eq!(res.text, expected);
+	assert!(res.text.starts_with("meant to learn"));
+}
+
 #[test]
 fn iter_start() {
 	let mut chain = MarkovChain::new(2);
@@ -318,20 +440,367 @@ fn iter_start() {
 	}
 }
 
+#[test]
+fn memory_usage1() {
+	let mut chain = MarkovChain::new(3);
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+
+	let usage = chain.memory_usage();
+
+	assert_eq!(usage.states_per_order.len(), 3);
+	assert_eq!(usage.states_per_order.iter().sum::<usize>(), chain.len());
+	assert_eq!(usage.inline_keys, chain.len());
+	assert_eq!(usage.spilled_keys, 0);
+	assert!(usage.total() > 0);
+}
+
+#[test]
+fn memory_usage2() {
+	let mut chain = RawMarkovChain::<1>::new(3);
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+
+	let usage = chain.memory_usage();
+
+	assert_eq!(usage.inline_keys, usage.states_per_order[0]);
+	assert_eq!(
+		usage.spilled_keys,
+		usage.states_per_order[1] + usage.states_per_order[2]
+	);
+	assert!(usage.spilled_key_bytes > 0);
+}
+
+#[test]
+fn inspect_states() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+
+	assert_eq!(chain.states().count(), chain.len());
+	for state in chain.states() {
+		assert!(state.len() <= 2);
+		assert_eq!(
+			state.successors().iter().map(|(_, c)| c).sum::<usize>(),
+			state.total()
+		);
+		assert!(chain.contains_state(state.tokens()));
+	}
+}
+
+#[test]
+fn inspect_lookup() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(word_regex_matches(TEST_TEXT));
+
+	assert_eq!(
+		chain.successors(["Haze", "and"]),
+		Some(vec![("Mist", 1), ("not", 1), ("Mist.", 1)])
+	);
+	assert_eq!(chain.successors(["level"]).map(|s| s.len()), Some(2));
+	assert_eq!(chain.successors(["Charizard"]), None);
+	assert!(chain.contains_state(["Vaporeon"]));
+	assert!(!chain.contains_state(["Vaporeon", "Vaporeon"]));
+
+	assert_eq!(chain.token_count("Vaporeon"), 4);
+	assert_eq!(chain.token_count("Charizard"), 0);
+	assert_eq!(chain.vocabulary().count(), chain.cache_len());
+	assert_eq!(chain.vocabulary().next(), Some("Hey"));
+}
+
+#[test]
+fn corpus_stats() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens("a b a c a b".split(' '));
+
+	let stats = chain.corpus_stats();
+
+	assert_eq!(stats.vocabulary, 3);
+	assert_eq!(stats.orders.len(), 2);
+
+	// a -> c b, b -> a, c -> a
+	let first = &stats.orders[0];
+	assert_eq!(first.order, 1);
+	assert_eq!(first.states, 3);
+	assert_eq!(first.transitions, 4);
+	assert_eq!(first.branching, vec![(1, 2), (2, 1)]);
+	assert!((first.mean_branching - 4.0 / 3.0).abs() < 1e-9);
+	assert!((first.deterministic - 2.0 / 3.0).abs() < 1e-9);
+	assert!((first.conditional_entropy - 0.5).abs() < 1e-9);
+
+	// every state of size 2 is seen once
+	let second = &stats.orders[1];
+	assert_eq!(second.states, 4);
+	assert_eq!(second.deterministic, 1.0);
+	assert_eq!(second.conditional_entropy, 0.0);
+}
+
+#[test]
+fn capacity() {
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens(word_regex_matches(TEST_TEXT));
+	let mut chain2 = MarkovChain::with_capacity(2, 100_000);
+	chain2.add_tokens(word_regex_matches(TEST_TEXT));
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(10, &mut rng1),
+			chain2.generate(10, &mut rng2)
+		);
+	}
+}
+
 #[cfg(feature = "serialize")]
 #[test]
 fn serde() {
-	let mut chain1 = MarkovChain::new(2, Regex::new(WORD_REGEX).unwrap());
-	chain1.add_text(TEST_TEXT);
-	let chain2 = chain1.clone();
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens(word_regex_matches(TEST_TEXT));
+	let chain2: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1).unwrap()).unwrap();
 
 	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
 	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
 
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(10, &mut rng1),
+			chain2.generate(10, &mut rng2)
+		);
+	}
+}
+
+#[test]
+fn canonicalize() {
+	let first = word_regex_matches(TEST_TEXT);
+	let second = word_regex_matches("Vaporeon learns Haze and Mist at level 42.");
+
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens(&first);
+	chain1.add_tokens(&second);
+	let mut chain2 = MarkovChain::with_capacity(2, 1000);
+	chain2.add_tokens(&second);
+	chain2.add_tokens(&first);
+
+	chain1.canonicalize();
+	chain2.canonicalize();
+
+	assert!(chain1.vocabulary().is_sorted());
 	assert_eq!(
-		chain1.generate(10, &mut rng1),
-		chain2.generate(10, &mut rng2)
-	)
+		chain1.vocabulary().collect::<Vec<_>>(),
+		chain2.vocabulary().collect::<Vec<_>>()
+	);
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(10, &mut rng1),
+			chain2.generate(10, &mut rng2)
+		);
+	}
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn serde_canonical() {
+	let first = word_regex_matches(TEST_TEXT);
+	let second = word_regex_matches("Vaporeon learns Haze and Mist at level 42.");
+
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens(&first);
+	chain1.add_tokens(&second);
+	let mut chain2 = MarkovChain::with_capacity(2, 1000);
+	chain2.add_tokens(&second);
+	chain2.add_tokens(&first);
+
+	let json1 = serde_json::to_string(&chain1.canonical()).unwrap();
+	let json2 = serde_json::to_string(&chain2.canonical()).unwrap();
+	assert_eq!(json1, json2);
+
+	// canonical output is a regular chain
+	let chain3: MarkovChain = serde_json::from_str(&json1).unwrap();
+	assert_eq!(serde_json::to_string(&chain3.canonical()).unwrap(), json1);
+
+	chain1.canonicalize();
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng3 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(10, &mut rng1),
+			chain3.generate(10, &mut rng3)
+		);
+	}
+}
+
+#[test]
+fn normalization() {
+	let mut chain = MarkovChain::with_normalization(1, Normalization::ALL);
+	chain.add_tokens(
+		"The cat sat. the cat ran. THE CAFE\u{301} opened. the café closed.".split(' '),
+	);
+
+	assert_eq!(chain.normalization(), Normalization::ALL);
+	assert_eq!(chain.token_count("tHe"), 4);
+	assert_eq!(chain.token_count("cafe"), 2);
+	assert_eq!(chain.surface_form("THE"), Some("the"));
+	assert_eq!(chain.surface_form("CAFÉ"), Some("CAFE\u{301}"));
+	assert!(chain.contains_state(["CAT"]));
+	assert_eq!(chain.match_seed(["Café"]).matched, 1);
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		let res = chain
+			.generate_start_strict(["THE", "Cat"], 2, &mut rng)
+			.unwrap();
+		assert_eq!(res.matched, 1);
+		assert!(["sat. the", "ran. the"].contains(&res.text.as_str()));
+	}
+
+	assert_eq!(Normalization::NONE.apply("CAFÉ"), "CAFÉ");
+	assert_eq!(
+		Normalization {
+			nfc: true,
+			..Default::default()
+		}
+		.apply("CAFE\u{301}"),
+		"CAFÉ"
+	);
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn serde_normalization() {
+	let mut chain1 = MarkovChain::with_normalization(2, Normalization::ALL);
+	chain1.add_tokens(word_regex_matches(TEST_TEXT));
+	let chain2: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1).unwrap()).unwrap();
+	let chain3: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1.canonical()).unwrap()).unwrap();
+
+	assert_eq!(chain2.normalization(), Normalization::ALL);
+	assert_eq!(chain3.surface_form("vaporeon"), Some("Vaporeon"));
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate_start(["IN", "YELLOW,"], 10, &mut rng1),
+			chain2.generate_start(["in", "yellow,"], 10, &mut rng2)
+		);
+	}
+}
+
+#[test]
+fn tagged() {
+	let mut chain = MarkovChain::new(1);
+	chain.add_tokens_tagged("the cat sat on the mat".split(' '), "kids");
+	chain.add_tokens_tagged("the dog ate the bone".split(' '), "pets");
+	chain.add_tokens("the end".split(' '));
+
+	assert_eq!(chain.tags().collect::<Vec<_>>(), ["kids", "pets"]);
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let kids = TagWeights::only(["kids"]);
+	for _ in 0..20 {
+		let res = chain.generate_start_tagged(["the"], 1, &kids, &mut rng);
+		assert!(["cat", "mat"].contains(&res.unwrap().as_str()));
+	}
+
+	// unknown contexts fall back to the states with allowed transitions
+	let pets = TagWeights::new().with("pets", 1.0);
+	for _ in 0..20 {
+		let res = chain.generate_tagged(1, &pets, &mut rng).unwrap();
+		assert!(["dog", "ate", "the", "bone"].contains(&res.as_str()));
+	}
+
+	let untagged = TagWeights::new().with_untagged(1.0);
+	assert_eq!(
+		chain.generate_start_tagged(["the"], 1, &untagged, &mut rng)
+			.unwrap(),
+		"end"
+	);
+	assert_eq!(
+		chain.generate_start_tagged(["the"], 5, &TagWeights::new(), &mut rng)
+			.unwrap(),
+		""
+	);
+
+	// mixed weights follow the counts times the weights
+	let mixed = TagWeights::only(["kids", "pets"]).with("pets", 3.0);
+	let dogs = chain
+		.iter_start_tagged(["on"], 1000, &mixed, &mut rng)
+		.filter(|&s| s == "dog" || s == "bone")
+		.count();
+	assert!(dogs > 0);
+
+	// huge weights don't overflow, and weights that are not finite disable the tag
+	let huge = TagWeights::only(["kids", "pets"])
+		.with("kids", f64::MAX)
+		.with("pets", f64::MAX);
+	let res: Vec<_> = chain
+		.iter_start_tagged(["the"], 1000, &huge, &mut rng)
+		.collect();
+	assert!(res.contains(&"cat"));
+	assert!(res.contains(&"dog"));
+	let infinite = TagWeights::new()
+		.with("kids", f64::INFINITY)
+		.with("pets", 1.0)
+		.with_untagged(f64::NAN);
+	for _ in 0..20 {
+		let res = chain.generate_start_tagged(["the"], 1, &infinite, &mut rng);
+		assert!(["dog", "bone"].contains(&res.unwrap().as_str()));
+	}
+
+	// untagged generation uses every tag
+	assert_eq!(chain.successors(["the"]).unwrap().len(), 5);
+}
+
+#[test]
+fn hasher() {
+	let mut chain1 = MarkovChain::new(3);
+	chain1.add_tokens(word_regex_matches(TEST_TEXT));
+	let mut chain2: RawMarkovChain<4, std::hash::RandomState> = ChainBuilder::new(3)
+		.build_with_hasher(std::hash::RandomState::new())
+		.unwrap();
+	chain2.add_tokens(word_regex_matches(TEST_TEXT));
+
+	assert_eq!(chain1.len(), chain2.len());
+
+	// generation doesn't depend on the order of the hash table
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(20, &mut rng1),
+			chain2.generate(20, &mut rng2)
+		);
+	}
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn serde_tagged() {
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens_tagged(word_regex_matches(TEST_TEXT), "vaporeon");
+	chain1.add_tokens(word_regex_matches(
+		"Vaporeon learns Haze and Mist at level 42.",
+	));
+	let chain2: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1).unwrap()).unwrap();
+	let chain3: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1.canonical()).unwrap()).unwrap();
+
+	assert_eq!(chain3.tags().collect::<Vec<_>>(), ["vaporeon"]);
+
+	let weights = TagWeights::only(["vaporeon"]);
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate_tagged(10, &weights, &mut rng1),
+			chain2.generate_tagged(10, &weights, &mut rng2)
+		);
+	}
 }
 
 fn word_regex_matches(s: &'static str) -> Vec<&'static str> {
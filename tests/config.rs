@@ -0,0 +1,151 @@
+use markov_str::*;
+use rand::SeedableRng;
+use regex::Regex;
+
+const TEST_TEXT: &str = "Hey guys, did you know that Vaporeon can learn Mist in Yellow, but only under a very specific circumstance? In Yellow, Vaporeon is meant to learn both Haze and Mist at level 42.";
+
+#[test]
+fn builder_validation() {
+	assert!(matches!(
+		ChainBuilder::new(0).build::<4>(),
+		Err(MarkovError::InvalidConfig(_))
+	));
+	assert!(MarkovChain::builder(2).min_order(0).build::<4>().is_err());
+	assert!(MarkovChain::builder(2).min_order(3).build::<4>().is_err());
+
+	let chain: MarkovChain = ChainBuilder::new(2)
+		.capacity(100)
+		.min_order(2)
+		.normalization(Normalization::ALL)
+		.fallback(FallbackPolicy::Stop)
+		.min_count(2)
+		.tokenizer(Tokenizer::Words)
+		.build()
+		.unwrap();
+
+	assert_eq!(chain.state_size(), 2);
+	assert_eq!(
+		chain.config(),
+		&ChainConfig {
+			min_order: 2,
+			sentence_boundaries: false,
+			normalization: Normalization::ALL,
+			fallback: FallbackPolicy::Stop,
+			min_count: 2,
+			tokenizer: Tokenizer::Words,
+			limits: Limits::default(),
+		}
+	);
+}
+
+#[test]
+fn min_order() {
+	let mut chain: MarkovChain = ChainBuilder::new(3).min_order(2).build().unwrap();
+	chain.add_text(TEST_TEXT);
+
+	assert_eq!(chain.memory_usage().states_per_order[0], 0);
+	assert!(chain.states().all(|s| s.len() >= 2));
+	assert!(!chain.contains_state(["Vaporeon"]));
+	assert!(chain.contains_state(["Vaporeon", "is"]));
+}
+
+#[test]
+fn sentence_boundaries() {
+	let mut chain: MarkovChain = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.tokenizer(Tokenizer::Words)
+		.build()
+		.unwrap();
+	chain.add_text("The cat sat on the mat. A dog ate the bone! The cat ran away.");
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..20 {
+		let res = chain.generate(100, &mut rng).unwrap();
+
+		assert!(res.starts_with("The") || res.starts_with("A"), "{}", res);
+		assert!(res.ends_with(['.', '!']), "{}", res);
+		assert!(!res.contains(SENTENCE_START) && !res.contains(SENTENCE_END));
+	}
+
+	// seeds continue until the end of the sentence
+	assert_eq!(
+		chain.generate_start(["dog"], 100, &mut rng).unwrap(),
+		"ate the bone!"
+	);
+	assert!(matches!(
+		chain.try_generate_start(["dog"], 100, &mut rng),
+		Err(MarkovError::DeadEnd { generated: 3 })
+	));
+}
+
+#[test]
+fn fallback_stop() {
+	let mut chain: MarkovChain = ChainBuilder::new(2)
+		.fallback(FallbackPolicy::Stop)
+		.build()
+		.unwrap();
+	chain.add_text("the cat sat");
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	assert_eq!(chain.generate_start(["cat"], 10, &mut rng).unwrap(), "sat");
+	assert_eq!(
+		chain.try_generate_start(["sat"], 10, &mut rng),
+		Err(MarkovError::DeadEnd { generated: 0 })
+	);
+	// generation without a seed still starts from a random state
+	assert!(!chain.generate(10, &mut rng).unwrap().is_empty());
+}
+
+#[test]
+fn prune() {
+	let mut chain: MarkovChain = ChainBuilder::new(1).min_count(2).build().unwrap();
+	chain.add_text("a b a b a c");
+	chain.add_text("c d");
+
+	chain.prune();
+
+	assert_eq!(chain.successors(["a"]).unwrap(), [("b", 2)]);
+	assert!(!chain.contains_state(["c"]));
+	assert_eq!(chain.token_count("c"), 2);
+}
+
+#[test]
+fn tokenizer_words() {
+	let regex = Regex::new(WORD_REGEX).unwrap();
+	let expected: Vec<_> = regex.find_iter(TEST_TEXT).map(|m| m.as_str()).collect();
+
+	assert_eq!(Tokenizer::Words.tokenize(TEST_TEXT), expected);
+	assert_eq!(
+		Tokenizer::Whitespace.tokenize(" a  b\tc\n"),
+		["a", "b", "c"]
+	);
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn serde_config() {
+	let mut chain1: MarkovChain = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.fallback(FallbackPolicy::Stop)
+		.tokenizer(Tokenizer::Words)
+		.build()
+		.unwrap();
+	chain1.add_text(TEST_TEXT);
+
+	let chain2: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1).unwrap()).unwrap();
+	let chain3: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1.canonical()).unwrap()).unwrap();
+
+	assert_eq!(chain2.config(), chain1.config());
+	assert_eq!(chain3.config(), chain1.config());
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(100, &mut rng1),
+			chain2.generate(100, &mut rng2)
+		);
+	}
+}
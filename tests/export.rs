@@ -0,0 +1,96 @@
+use markov_str::*;
+
+fn chain() -> MarkovChain {
+	let mut chain = MarkovChain::new(1);
+	chain.add_tokens("a b a c a b".split(' '));
+	chain
+}
+
+#[test]
+fn dot() {
+	let mut out = Vec::new();
+	chain().write_dot(&mut out, &GraphOptions::default())
+		.unwrap();
+
+	assert_eq!(
+		String::from_utf8(out).unwrap(),
+		"digraph markov_chain {
+	n0 [label=\"a\"];
+	n1 [label=\"b\"];
+	n2 [label=\"c\"];
+	n0 -> n1 [label=\"0.667\", weight=2];
+	n0 -> n2 [label=\"0.333\", weight=1];
+	n1 -> n0 [label=\"1.000\", weight=1];
+	n2 -> n0 [label=\"1.000\", weight=1];
+}
+"
+	);
+}
+
+#[test]
+fn dot_filters() {
+	let options = GraphOptions {
+		max_nodes: Some(1),
+		min_probability: 0.5,
+		order: Some(1),
+	};
+	let mut out = Vec::new();
+	chain().write_dot(&mut out, &options).unwrap();
+	let out = String::from_utf8(out).unwrap();
+
+	assert!(out.contains("n0 [label=\"a\"]"));
+	assert!(!out.contains("n1"));
+	assert!(!out.contains("->"));
+
+	let mut out = Vec::new();
+	let options = GraphOptions {
+		order: Some(2),
+		..Default::default()
+	};
+	chain().write_dot(&mut out, &options).unwrap();
+
+	assert_eq!(
+		String::from_utf8(out).unwrap(),
+		"digraph markov_chain {\n}\n"
+	);
+}
+
+#[test]
+fn graphml() {
+	let mut chain = MarkovChain::new(1);
+	chain.add_tokens(["<a>", "&"]);
+
+	let mut out = Vec::new();
+	chain.write_graphml(&mut out, &GraphOptions::default())
+		.unwrap();
+	let out = String::from_utf8(out).unwrap();
+
+	assert!(out.starts_with("<?xml"));
+	assert!(out.contains("<node id=\"n0\"><data key=\"label\">&lt;a&gt;</data></node>"));
+	assert!(out.contains("<node id=\"n1\"><data key=\"label\">&amp;</data></node>"));
+	assert!(out.contains("<edge source=\"n0\" target=\"n1\"><data key=\"count\">1</data><data key=\"probability\">1</data></edge>"));
+	assert!(out.trim_end().ends_with("</graphml>"));
+}
+
+#[test]
+fn sentence_markers() {
+	let mut chain: MarkovChain = ChainBuilder::new(1)
+		.sentence_boundaries(true)
+		.build()
+		.unwrap();
+	chain.add_tokens(["hello"]);
+
+	let mut out = Vec::new();
+	chain.write_graphml(&mut out, &GraphOptions::default())
+		.unwrap();
+	let out = String::from_utf8(out).unwrap();
+	assert!(out.contains("<data key=\"label\">&lt;s&gt;</data>"));
+	assert!(out.contains("<data key=\"label\">&lt;/s&gt;</data>"));
+	assert!(!out.contains(SENTENCE_START) && !out.contains(SENTENCE_END));
+
+	let mut out = Vec::new();
+	chain.write_dot(&mut out, &GraphOptions::default()).unwrap();
+	let out = String::from_utf8(out).unwrap();
+	assert!(out.contains("[label=\"<s>\"]"));
+	assert!(out.contains("[label=\"</s>\"]"));
+}
@@ -0,0 +1,78 @@
+use markov_str::*;
+use rand::SeedableRng;
+
+const TEXT: &str = "the dog ate the bone and the cat ate the fish";
+
+#[test]
+fn ids_match_tokens() {
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens(TEXT.split(' '));
+
+	let mut chain2 = MarkovChain::new(2);
+	let ids: Vec<Spur> = TEXT.split(' ').map(|t| chain2.intern_token(t)).collect();
+	chain2.add_ids(ids.iter().copied());
+
+	assert_eq!(chain1.len(), chain2.len());
+	assert_eq!(chain2.token_count("the"), 4);
+	assert_eq!(chain2.token_id("the"), Some(ids[0]));
+	assert_eq!(chain2.id_token(ids[1]), Some("dog"));
+	assert_eq!(ids[1].into_usize(), 1);
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		let tokens: Vec<_> = chain1.iter(10, &mut rng1).collect();
+		let generated: Vec<_> = chain2
+			.iter_ids(10, &mut rng2)
+			.map(|id| chain2.id_token(id).unwrap())
+			.collect();
+		assert_eq!(tokens, generated);
+	}
+}
+
+#[test]
+fn iter_start_ids() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEXT.split(' '));
+
+	let start = [
+		chain.token_id("cat").unwrap(),
+		chain.token_id("ate").unwrap(),
+	];
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let next = chain.iter_start_ids(start, 1, &mut rng).next();
+	assert_eq!(next, chain.token_id("the"));
+}
+
+#[test]
+fn unknown_ids() {
+	let mut chain = MarkovChain::new(2);
+	let the = chain.intern_token("the");
+	let unknown = Spur::try_from_usize(5).unwrap();
+
+	assert_eq!(
+		chain.try_add_ids([the, unknown]),
+		Err(MarkovError::UnknownId(unknown))
+	);
+	chain.add_ids([the, unknown, the]);
+	assert!(chain.is_empty());
+	assert_eq!(chain.token_count("the"), 0);
+}
+
+#[test]
+fn sentence_boundaries() {
+	let mut chain: MarkovChain = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.normalization(Normalization::ALL)
+		.build()
+		.unwrap();
+	let ids: Vec<_> = ["The", "Dog", "ate."]
+		.into_iter()
+		.map(|t| chain.intern_token(t))
+		.collect();
+	chain.add_ids_weighted(ids, 2);
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	assert_eq!(chain.generate(10, &mut rng).unwrap(), "the dog ate.");
+	assert_eq!(chain.token_count("dog"), 2);
+}
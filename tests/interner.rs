@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use markov_str::*;
+use rand::SeedableRng;
+
+const TEXT1: &str = "the dog ate the bone and the cat ate the fish";
+const TEXT2: &str = "the cat saw the dog and the dog saw the bone";
+
+#[test]
+fn shared() {
+	let interner = Arc::new(ThreadedRodeo::new());
+	let mut chain1 = MarkovChain::with_interner(2, Arc::clone(&interner));
+	let mut chain2: MarkovChain = ChainBuilder::new(2)
+		.interner(Arc::clone(&interner))
+		.build()
+		.unwrap();
+	let chain3 = MarkovChain::new(2);
+
+	chain1.add_tokens(TEXT1.split(' '));
+	chain2.add_tokens(TEXT2.split(' '));
+
+	assert!(chain1.shares_interner(&chain2));
+	assert!(!chain1.shares_interner(&chain3));
+	assert!(chain3.shared_interner().is_none());
+	assert!(Arc::ptr_eq(chain1.shared_interner().unwrap(), &interner));
+
+	// every token is stored once, and the chains only count their own
+	assert_eq!(interner.len(), 8);
+	assert_eq!(chain1.cache_len(), 7);
+	assert_eq!(chain1.vocabulary().count(), 7);
+	assert_eq!(chain1.corpus_stats().vocabulary, 7);
+	assert_eq!(chain1.token_count("the"), 4);
+	assert_eq!(chain2.token_count("the"), 4);
+	assert_eq!(chain1.token_count("saw"), 0);
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		let text = chain2.generate(10, &mut rng).unwrap();
+		assert!(text.split(' ').all(|t| TEXT2.contains(t)), "{}", text);
+	}
+}
+
+#[test]
+fn shared_threads() {
+	let interner = Arc::new(ThreadedRodeo::new());
+	let mut chains: Vec<_> = (0..4)
+		.map(|_| MarkovChain::with_interner(2, Arc::clone(&interner)))
+		.collect();
+
+	std::thread::scope(|s| {
+		for (i, chain) in chains.iter_mut().enumerate() {
+			s.spawn(move || {
+				let text = if i % 2 == 0 { TEXT1 } else { TEXT2 };
+				chain.add_tokens(text.split(' '));
+			});
+		}
+	});
+
+	assert_eq!(interner.len(), 8);
+	assert!(chains.iter().all(|c| c.validate().is_ok()));
+	assert_eq!(chains[0].len(), chains[2].len());
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn shared_serde() {
+	let interner = Arc::new(ThreadedRodeo::new());
+	let mut chain1 = MarkovChain::with_interner(2, Arc::clone(&interner));
+	let mut chain2 = MarkovChain::with_interner(2, Arc::clone(&interner));
+	chain1.add_tokens(TEXT1.split(' '));
+	chain2.add_tokens(TEXT2.split(' '));
+
+	let chain3: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain2).unwrap()).unwrap();
+	assert!(chain3.shared_interner().is_none());
+
+	// tokens of the other chains are not serialized
+	let mut chain4 = MarkovChain::with_interner(2, Arc::clone(&interner));
+	chain4.add_tokens(["the", "end"]);
+	let json = serde_json::to_value(&chain4).unwrap();
+	assert_eq!(json["cache"], serde_json::json!(["the", "end"]));
+	let chain5: MarkovChain = serde_json::from_value(json).unwrap();
+	assert_eq!(chain5.vocabulary().collect::<Vec<_>>(), ["the", "end"]);
+	assert_eq!(chain5.token_count("end"), 1);
+	assert_eq!(chain5.successors(["the"]).unwrap(), [("end", 1)]);
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain2.generate(10, &mut rng1),
+			chain3.generate(10, &mut rng2)
+		);
+	}
+}
+
+#[cfg(feature = "serialize")]
+#[test]
+fn shared_canonical() {
+	let interner = Arc::new(ThreadedRodeo::new());
+	let mut other = MarkovChain::with_interner(2, Arc::clone(&interner));
+	other.add_tokens(TEXT2.split(' '));
+
+	let mut chain1 = MarkovChain::with_interner(2, Arc::clone(&interner));
+	let mut chain2 = MarkovChain::new(2);
+	chain1.add_tokens(TEXT1.split(' '));
+	chain2.add_tokens(TEXT1.split(' '));
+
+	// only the tokens of the chain are in its canonical form
+	assert_eq!(
+		serde_json::to_string(&chain1.canonical()).unwrap(),
+		serde_json::to_string(&chain2.canonical()).unwrap()
+	);
+
+	chain1.canonicalize();
+	assert!(chain1.shared_interner().is_none());
+	assert_eq!(chain1.cache_len(), 7);
+}
+
+#[test]
+fn shared_fallback() {
+	let interner = Arc::new(ThreadedRodeo::new());
+	let mut other = MarkovChain::with_interner(1, Arc::clone(&interner));
+	other.add_tokens("fish the ate cat".split(' '));
+
+	let mut chain1 = MarkovChain::with_interner(1, Arc::clone(&interner));
+	let mut chain2 = MarkovChain::new(1);
+	chain1.add_tokens(TEXT1.split(' '));
+	chain2.add_tokens(TEXT1.split(' '));
+
+	// states are picked by their tokens, not by the keys of the interner
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(10, &mut rng1),
+			chain2.generate(10, &mut rng2)
+		);
+	}
+}
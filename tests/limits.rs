@@ -0,0 +1,155 @@
+use markov_str::*;
+
+fn limited(limits: Limits) -> RawMarkovChain<4> {
+	ChainBuilder::new(2).limits(limits).build().unwrap()
+}
+
+fn assert_unchanged(chain: &RawMarkovChain<4>) {
+	assert_eq!(chain.vocabulary().count(), 0);
+	assert_eq!(chain.states().count(), 0);
+}
+
+#[test]
+fn token_length() {
+	let mut chain = limited(Limits {
+		max_token_len: Some(4),
+		..Limits::default()
+	});
+
+	assert_eq!(
+		chain.try_add_tokens(["the", "doggo", "ate"]),
+		Err(MarkovError::LimitExceeded {
+			kind: LimitKind::TokenLength,
+			limit: 4
+		})
+	);
+	assert_unchanged(&chain);
+
+	chain.try_add_tokens(["the", "dog", "ate"]).unwrap();
+	assert_eq!(chain.vocabulary().count(), 3);
+}
+
+#[test]
+fn vocabulary() {
+	let mut chain = limited(Limits {
+		max_vocabulary: Some(3),
+		..Limits::default()
+	});
+
+	chain.try_add_tokens(["the", "dog", "ate"]).unwrap();
+	chain.try_add_tokens(["the", "dog", "ate", "the"]).unwrap();
+	assert!(matches!(
+		chain.try_add_tokens(["the", "bone"]),
+		Err(MarkovError::LimitExceeded {
+			kind: LimitKind::Vocabulary,
+			..
+		})
+	));
+	assert_eq!(chain.vocabulary().count(), 3);
+}
+
+#[test]
+fn states() {
+	let mut chain = limited(Limits {
+		max_states: Some(2),
+		..Limits::default()
+	});
+
+	assert!(matches!(
+		chain.try_add_tokens(["a", "b", "c", "d", "e"]),
+		Err(MarkovError::LimitExceeded {
+			kind: LimitKind::States,
+			..
+		})
+	));
+	assert_unchanged(&chain);
+}
+
+#[test]
+fn weight() {
+	let mut chain = limited(Limits {
+		max_weight: Some(5),
+		..Limits::default()
+	});
+
+	assert!(chain.try_add_tokens_weighted(["a", "b", "c"], 6).is_err());
+	assert_unchanged(&chain);
+	chain.try_add_tokens_weighted(["a", "b", "c"], 5).unwrap();
+	assert_eq!(chain.token_count("a"), 5);
+}
+
+#[test]
+fn memory() {
+	let mut chain = limited(Limits {
+		max_memory: Some(chain_size() + 64),
+		..Limits::default()
+	});
+
+	assert!(matches!(
+		chain.try_add_tokens_weighted(["a", "b", "c"], 1000),
+		Err(MarkovError::LimitExceeded {
+			kind: LimitKind::Memory,
+			..
+		})
+	));
+	assert_unchanged(&chain);
+}
+
+#[test]
+fn memory_estimate() {
+	let limit = 1 << 20;
+	let mut chain = limited(Limits {
+		max_memory: Some(limit),
+		..Limits::default()
+	});
+
+	// training stops near the limit, without measuring the whole chain every time
+	let mut sentences = 0;
+	while chain
+		.try_add_tokens([
+			format!("word{}", sentences),
+			"and".to_string(),
+			format!("more{}", sentences % 97),
+		])
+		.is_ok()
+	{
+		sentences += 1;
+	}
+	let usage = chain.memory_usage().total();
+	assert!(sentences > 1000, "{}", sentences);
+	assert!(usage > limit / 2 && usage < limit * 2, "{}", usage);
+}
+
+fn chain_size() -> usize {
+	RawMarkovChain::<4>::new(2).memory_usage().total()
+}
+
+#[test]
+fn infallible_skips() {
+	let mut chain = limited(Limits {
+		max_token_len: Some(4),
+		..Limits::default()
+	});
+
+	chain.add_tokens(["the", "doggo", "ate"]);
+	assert_unchanged(&chain);
+
+	chain.add_text("the dog ate the bone");
+	assert_eq!(chain.vocabulary().count(), 4);
+}
+
+#[test]
+fn text_keeps_sentences() {
+	let mut chain: RawMarkovChain<4> = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.limits(Limits {
+			max_token_len: Some(6),
+			..Limits::default()
+		})
+		.build()
+		.unwrap();
+
+	assert!(chain.try_add_text("The dog ate. The mammoth ran.").is_err());
+	assert_eq!(chain.token_count("dog"), 1);
+	assert_eq!(chain.token_count("ran."), 0);
+}
@@ -0,0 +1,98 @@
+use markov_str::*;
+use rand::SeedableRng;
+
+#[test]
+fn mixture_weights() {
+	let mut cats = MarkovChain::new(1);
+	cats.add_tokens("the cat sat on the mat".split(' '));
+	let mut dogs = MarkovChain::new(2);
+	dogs.add_tokens("the dog ate the bone".split(' '));
+
+	let mut mixture = ChainMixture::new().with(&cats, 1.0).with(&dogs, 0.0);
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+
+	assert_eq!(mixture.len(), 2);
+	for _ in 0..20 {
+		let res = mixture.generate_start(["the"], 1, &mut rng).unwrap();
+		assert!(["cat", "mat"].contains(&res.as_str()));
+	}
+
+	mixture.set_weight(0, 0.0);
+	mixture.set_weight(1, 2.0);
+	assert_eq!(mixture.weights().collect::<Vec<_>>(), [0.0, 2.0]);
+	for _ in 0..20 {
+		let res = mixture.generate_start(["the"], 1, &mut rng).unwrap();
+		assert!(["dog", "bone"].contains(&res.as_str()));
+	}
+
+	// both chains know "the", so both contribute
+	mixture.set_weight(0, 1.0);
+	let res: Vec<_> = mixture.iter_start(["the"], 1000, &mut rng).collect();
+	assert!(res.contains(&"cat"));
+	assert!(res.contains(&"bone"));
+}
+
+#[test]
+fn mixture_huge_weights() {
+	let mut cats = MarkovChain::new(1);
+	cats.add_tokens("the cat sat on the mat".split(' '));
+	let mut dogs = MarkovChain::new(1);
+	dogs.add_tokens("the dog ate the bone".split(' '));
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+
+	// the sum of the weights is not finite
+	let mixture = ChainMixture::new()
+		.with(&cats, f64::MAX)
+		.with(&dogs, f64::MAX);
+	let res: Vec<_> = mixture.iter_start(["the"], 1000, &mut rng).collect();
+	assert!(res.contains(&"cat"));
+	assert!(res.contains(&"bone"));
+
+	// weights that are not finite are not used
+	let mixture = ChainMixture::new()
+		.with(&cats, f64::INFINITY)
+		.with(&dogs, f64::NAN);
+	assert_eq!(mixture.generate_start(["the"], 1, &mut rng), None);
+	let mixture = mixture.with(&dogs, 1.0);
+	for _ in 0..20 {
+		let res = mixture.generate_start(["the"], 1, &mut rng).unwrap();
+		assert!(["dog", "bone"].contains(&res.as_str()));
+	}
+}
+
+#[test]
+fn mixture_context() {
+	let mut cats = MarkovChain::new(1);
+	cats.add_tokens("the cat sat on the mat".split(' '));
+	let mut dogs = MarkovChain::new(2);
+	dogs.add_tokens("the dog ate the bone".split(' '));
+
+	let mixture = ChainMixture::new().with(&cats, 1.0).with(&dogs, 1.0);
+	let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+
+	// only the second chain knows "ate"
+	for _ in 0..20 {
+		assert_eq!(
+			mixture.generate_start(["dog", "ate"], 1, &mut rng).unwrap(),
+			"the"
+		);
+	}
+
+	// no chain knows the context, so the chains fall back
+	for _ in 0..20 {
+		let res = mixture.generate_start(["bird"], 5, &mut rng).unwrap();
+		assert_eq!(res.split(' ').count(), 5);
+	}
+}
+
+#[test]
+fn mixture_empty() {
+	let empty = MarkovChain::new(2);
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+
+	assert_eq!(ChainMixture::<4>::new().generate(10, &mut rng), None);
+	assert_eq!(
+		ChainMixture::new().with(&empty, 1.0).generate(10, &mut rng),
+		None
+	);
+}
@@ -0,0 +1,86 @@
+use markov_str::*;
+use rand::SeedableRng;
+
+const TEXT: &str = "The dog ate the bone. The cat ate the fish. A very long sentence is here, and it goes on and on for a while before it ends.";
+
+fn sentence_chain() -> MarkovChain {
+	let mut chain: MarkovChain = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.build()
+		.unwrap();
+	chain.add_text(TEXT);
+
+	chain
+}
+
+#[test]
+fn bounds() {
+	let chain = sentence_chain();
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+
+	let options = SentenceOptions {
+		max_tokens: 5,
+		tries: 100,
+		..SentenceOptions::default()
+	};
+	for _ in 0..10 {
+		let sentence = chain.make_sentence(&options, &mut rng).unwrap();
+		assert!(sentence.split(' ').count() <= 5, "{}", sentence);
+		assert!(sentence.ends_with('.'), "{}", sentence);
+	}
+
+	let options = SentenceOptions {
+		min_tokens: 10,
+		min_chars: 40,
+		tries: 100,
+		..SentenceOptions::default()
+	};
+	let sentence = chain.make_sentence(&options, &mut rng).unwrap();
+	assert!(sentence.split(' ').count() >= 10, "{}", sentence);
+	assert!(sentence.len() >= 40, "{}", sentence);
+}
+
+#[test]
+fn errors() {
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let chain = sentence_chain();
+
+	let options = SentenceOptions {
+		max_chars: 3,
+		..SentenceOptions::default()
+	};
+	assert_eq!(
+		chain.make_sentence(&options, &mut rng),
+		Err(MarkovError::NoSentence { tries: 10 })
+	);
+
+	let options = SentenceOptions {
+		min_tokens: 6,
+		max_tokens: 5,
+		..SentenceOptions::default()
+	};
+	assert!(matches!(
+		chain.make_sentence(&options, &mut rng),
+		Err(MarkovError::InvalidConfig(_))
+	));
+
+	let mut chain = MarkovChain::new(2);
+	assert!(matches!(
+		chain.make_sentence(&SentenceOptions::default(), &mut rng),
+		Err(MarkovError::InvalidConfig(_))
+	));
+	chain.add_text(TEXT);
+	assert!(matches!(
+		chain.make_sentence(&SentenceOptions::default(), &mut rng),
+		Err(MarkovError::InvalidConfig(_))
+	));
+
+	let chain: MarkovChain = ChainBuilder::new(2)
+		.sentence_boundaries(true)
+		.build()
+		.unwrap();
+	assert_eq!(
+		chain.make_sentence(&SentenceOptions::default(), &mut rng),
+		Err(MarkovError::EmptyModel)
+	);
+}
@@ -0,0 +1,308 @@
+#![cfg(feature = "serialize")]
+
+use bincode::Options;
+use markov_str::*;
+use rand::SeedableRng;
+
+const TEST_TEXT: &str = "Hey guys, did you know that Vaporeon can learn Mist in Yellow, but only under a very specific circumstance? In Yellow, Vaporeon is meant to learn both Haze and Mist at level 42.";
+
+/// Returns a chain that uses every serialized field.
+fn test_chain() -> MarkovChain {
+	let mut chain: MarkovChain = ChainBuilder::new(6)
+		.sentence_boundaries(true)
+		.normalization(Normalization::ALL)
+		.tokenizer(Tokenizer::Words)
+		.build()
+		.unwrap();
+	chain.add_text(TEST_TEXT);
+	chain.add_tokens_tagged(TEST_TEXT.split(' '), "raw");
+
+	chain
+}
+
+fn assert_same(chain1: &MarkovChain, chain2: &MarkovChain) {
+	assert_eq!(chain1.len(), chain2.len());
+	assert_eq!(chain1.config(), chain2.config());
+	assert_eq!(
+		chain1.tags().collect::<Vec<_>>(),
+		chain2.tags().collect::<Vec<_>>()
+	);
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	let raw = TagWeights::only(["raw"]);
+	for _ in 0..10 {
+		assert_eq!(
+			chain1.generate(20, &mut rng1),
+			chain2.generate(20, &mut rng2)
+		);
+		assert_eq!(
+			chain1.generate_tagged(20, &raw, &mut rng1),
+			chain2.generate_tagged(20, &raw, &mut rng2)
+		);
+	}
+}
+
+#[test]
+fn json() {
+	let chain1 = test_chain();
+	let chain2: MarkovChain =
+		serde_json::from_str(&serde_json::to_string(&chain1).unwrap()).unwrap();
+
+	assert_same(&chain1, &chain2);
+}
+
+#[test]
+fn bincode() {
+	let chain1 = test_chain();
+	let chain2: MarkovChain =
+		bincode::deserialize(&bincode::serialize(&chain1).unwrap()).unwrap();
+
+	assert_same(&chain1, &chain2);
+}
+
+#[test]
+fn postcard() {
+	let chain1 = test_chain();
+	let chain2: MarkovChain =
+		postcard::from_bytes(&postcard::to_allocvec(&chain1).unwrap()).unwrap();
+
+	assert_same(&chain1, &chain2);
+}
+
+#[test]
+fn messagepack() {
+	let chain1 = test_chain();
+	let chain2: MarkovChain =
+		rmp_serde::from_slice(&rmp_serde::to_vec(&chain1).unwrap()).unwrap();
+
+	assert_same(&chain1, &chain2);
+}
+
+#[test]
+fn cbor() {
+	let chain1 = test_chain();
+	let mut bytes = Vec::new();
+	ciborium::into_writer(&chain1, &mut bytes).unwrap();
+	let chain2: MarkovChain = ciborium::from_reader(bytes.as_slice()).unwrap();
+
+	assert_same(&chain1, &chain2);
+}
+
+#[test]
+fn bincode_canonical() {
+	let mut chain1 = MarkovChain::new(2);
+	chain1.add_tokens(TEST_TEXT.split(' '));
+	let mut chain2 = MarkovChain::with_capacity(2, 1000);
+	chain2.add_tokens(TEST_TEXT.split(' ').rev());
+	chain2.add_tokens(TEST_TEXT.split(' '));
+	chain1.add_tokens(TEST_TEXT.split(' ').rev());
+
+	let bytes1 = bincode::serialize(&chain1.canonical()).unwrap();
+	let bytes2 = bincode::serialize(&chain2.canonical()).unwrap();
+	assert_eq!(bytes1, bytes2);
+
+	let mut chain3: MarkovChain = bincode::deserialize(&bytes1).unwrap();
+	chain1.canonicalize();
+	chain3.canonicalize();
+	assert_same(&chain1, &chain3);
+}
+
+const LEGACY_TEXT: &str = "Vaporeon can learn Mist in Yellow. In Yellow, Vaporeon is meant to learn both Haze and Mist at level 42.";
+
+/// Tokens of a state and its successors.
+type StateEntry<'a> = (Vec<&'a str>, Vec<(&'a str, usize)>);
+
+/// Returns every state with its successors, sorted.
+fn sorted_states(chain: &MarkovChain) -> Vec<StateEntry<'_>> {
+	let mut res: Vec<_> = chain
+		.states()
+		.map(|s| {
+			let mut successors = s.successors();
+			successors.sort_unstable();
+			(s.tokens().collect(), successors)
+		})
+		.collect();
+	res.sort_unstable();
+
+	res
+}
+
+#[test]
+fn versioned() {
+	let chain1 = test_chain();
+
+	let json = serde_json::to_string(&chain1.versioned()).unwrap();
+	assert!(json.starts_with(&format!(r#"{{"version":{},"chain":"#, FORMAT_VERSION)));
+	let chain2 =
+		MarkovChain::deserialize_versioned(&mut serde_json::Deserializer::from_str(&json))
+			.unwrap();
+	assert_same(&chain1, &chain2);
+
+	let bytes = bincode::serialize(&chain1.versioned()).unwrap();
+	let chain3 = MarkovChain::deserialize_versioned(&mut bincode::Deserializer::from_slice(
+		&bytes,
+		bincode::options().with_fixint_encoding(),
+	))
+	.unwrap();
+	assert_same(&chain1, &chain3);
+}
+
+#[test]
+fn versioned_unsupported() {
+	let json = format!(r#"{{"version":{},"chain":{{}}}}"#, FORMAT_VERSION + 1);
+	let err =
+		MarkovChain::deserialize_versioned(&mut serde_json::Deserializer::from_str(&json))
+			.err()
+			.unwrap();
+	assert!(err.to_string().starts_with(&format!(
+		"unsupported format version {}",
+		FORMAT_VERSION + 1
+	)));
+
+	let json = r#"{"chain":{},"version":1}"#;
+	assert!(
+		MarkovChain::deserialize_versioned(&mut serde_json::Deserializer::from_str(json))
+			.is_err()
+	);
+}
+
+#[test]
+fn legacy() {
+	let mut expected = MarkovChain::new(2);
+	expected.add_tokens(LEGACY_TEXT.split_whitespace());
+
+	for json in [
+		include_str!("legacy/markov_str-0.2.0.json"),
+		include_str!("legacy/markov_str-0.3.0.json"),
+		include_str!("legacy/markov_str-0.3.0-without-regex.json"),
+	] {
+		let chain1 = MarkovChain::deserialize_versioned(
+			&mut serde_json::Deserializer::from_str(json),
+		)
+		.unwrap();
+		let chain2 = MarkovChain::deserialize_legacy(
+			&mut serde_json::Deserializer::from_str(json),
+		)
+		.unwrap();
+
+		assert_eq!(chain1.state_size(), 2);
+		assert_eq!(sorted_states(&chain1), sorted_states(&expected));
+		assert_eq!(sorted_states(&chain2), sorted_states(&expected));
+		for chain in [&chain1, &chain2] {
+			assert_eq!(chain.vocabulary().count(), expected.vocabulary().count());
+			for token in expected.vocabulary() {
+				assert_eq!(
+					chain.token_count(token),
+					expected.token_count(token),
+					"{token}"
+				);
+			}
+		}
+
+		let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+		assert_eq!(
+			chain1.generate_start(["Vaporeon", "is"], 3, &mut rng)
+				.unwrap(),
+			"meant to learn"
+		);
+	}
+}
+
+/// Deserializes the JSON of a small chain after changing it with `f`, returns the error message.
+fn tampered(f: impl FnOnce(&mut serde_json::Value)) -> String {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens_tagged("a b c d".split(' '), "tag");
+	assert!(chain.validate().is_ok());
+
+	let mut json = serde_json::to_value(&chain).unwrap();
+	f(&mut json);
+
+	serde_json::from_value::<MarkovChain>(json)
+		.err()
+		.map(|e| e.to_string())
+		.unwrap_or_default()
+}
+
+#[test]
+fn validate_on_deserialize() {
+	assert_eq!(tampered(|_| {}), "");
+
+	// entries are [key, {items, tags}] pairs
+	let err = tampered(|j| j["items"][0][1]["items"][0] = 99.into());
+	assert!(
+		err.contains("refers to token 98, but there are 4 tokens"),
+		"{}",
+		err
+	);
+
+	let err = tampered(|j| j["items"][0][0] = serde_json::json!([1, 2, 3]));
+	assert!(
+		err.contains("has 3 tokens, but the state size is 2"),
+		"{}",
+		err
+	);
+
+	let err = tampered(|j| {
+		j["items"][0][1] = serde_json::json!({"items": [], "tags": []});
+	});
+	assert!(err.contains("has no successor"), "{}", err);
+
+	let err = tampered(|j| j["items"][0][1]["tags"] = serde_json::json!([1, 1]));
+	assert!(err.contains("has 1 successors, but 2 tags"), "{}", err);
+
+	let err = tampered(|j| j["items"][0][1]["tags"] = serde_json::json!([5]));
+	assert!(
+		err.contains("refers to tag 4, but there are 1 tags"),
+		"{}",
+		err
+	);
+
+	let err = tampered(|j| j["config"]["min_order"] = 3.into());
+	assert!(err.contains("minimum order is 3"), "{}", err);
+
+	let err = tampered(|j| j["state_size"] = 1_099_511_627_776u64.into());
+	assert!(err.contains("state size is 1099511627776"), "{}", err);
+
+	// versioned and legacy chains are checked too
+	let json = r#"{"version":1,"chain":{"items":[[[1],{"items":[7]}]],"state_size":1,"cache":["a"]}}"#;
+	assert!(
+		MarkovChain::deserialize_versioned(&mut serde_json::Deserializer::from_str(json))
+			.is_err()
+	);
+	let json = r#"{"items":{"[1]":[7]},"state_size":1,"regex":"","cache":["a"]}"#;
+	assert!(
+		MarkovChain::deserialize_legacy(&mut serde_json::Deserializer::from_str(json))
+			.is_err()
+	);
+}
+
+#[test]
+fn saturating_counts() {
+	let json = r#"{"items":[[[1],{"items":[2]}]],"state_size":1,"cache":["a","b"],"token_counts":[18446744073709551615,1]}"#;
+	let mut chain: MarkovChain = serde_json::from_str(json).unwrap();
+	chain.add_tokens(["a", "b"]);
+
+	assert_eq!(chain.token_count("a"), usize::MAX);
+	assert_eq!(chain.token_count("b"), 2);
+	assert!(chain.write_arpa(&mut Vec::new()).is_ok());
+}
+
+#[test]
+fn hasher_independent() {
+	let chain1 = test_chain();
+	let json = serde_json::to_string(&chain1).unwrap();
+
+	let chain2: RawMarkovChain<4, std::hash::RandomState> =
+		serde_json::from_str(&json).unwrap();
+	assert_eq!(
+		serde_json::to_string(&chain2.canonical()).unwrap(),
+		serde_json::to_string(&chain1.canonical()).unwrap()
+	);
+
+	let json = serde_json::to_string(&chain2.versioned()).unwrap();
+	let chain3 =
+		MarkovChain::deserialize_versioned(&mut serde_json::Deserializer::from_str(&json))
+			.unwrap();
+	assert_same(&chain1, &chain3);
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "stream")]
+
+use std::sync::Arc;
+
+use futures::{executor::block_on, StreamExt};
+use markov_str::*;
+use rand::SeedableRng;
+
+const TEST_TEXT: &str = "Hey guys, did you know that Vaporeon can learn Mist in Yellow, but only under a very specific circumstance? In Yellow, Vaporeon is meant to learn both Haze and Mist at level 42.";
+
+#[test]
+fn stream() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEST_TEXT.split(' '));
+	let chain = Arc::new(chain);
+
+	let rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let expected: Vec<String> = chain.iter_owned(50, rng).collect();
+
+	let rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let res: Vec<String> = block_on(chain.stream(50, rng).collect());
+
+	assert_eq!(res.len(), 50);
+	assert_eq!(res, expected);
+}
+
+#[test]
+fn stream_start() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEST_TEXT.split(' '));
+	let chain = Arc::new(chain);
+
+	let rng = rand::rngs::StdRng::seed_from_u64(42);
+	let mut stream = chain.stream_start(["Vaporeon", "is"], 10, rng);
+
+	assert_eq!(block_on(stream.next()).as_deref(), Some("meant"));
+	assert_eq!(block_on(stream.count()), 9);
+}
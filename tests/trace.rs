@@ -0,0 +1,57 @@
+use markov_str::*;
+use rand::SeedableRng;
+
+const TEXT: &str = "the dog ate the bone and the cat ate the fish";
+
+#[test]
+fn same_tokens() {
+	let mut chain = MarkovChain::new(3);
+	chain.add_tokens(TEXT.split(' '));
+
+	let mut rng1 = rand::rngs::StdRng::seed_from_u64(1337);
+	let mut rng2 = rand::rngs::StdRng::seed_from_u64(1337);
+	for _ in 0..10 {
+		let tokens: Vec<_> = chain.iter(20, &mut rng1).collect();
+		let traced: Vec<_> = chain.iter_traced(20, &mut rng2).map(|s| s.token).collect();
+		assert_eq!(tokens, traced);
+	}
+}
+
+#[test]
+fn steps() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEXT.split(' '));
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let steps: Vec<_> = chain.iter_start_traced(["cat"], 3, &mut rng).collect();
+
+	// [cat] -> ate, [cat, ate] -> the, [ate, the] -> bone or fish
+	assert_eq!(steps[0].token, "ate");
+	assert_eq!(steps[0].context, ["cat"]);
+	assert_eq!(steps[0].probability, 1.0);
+	assert_eq!(steps[0].candidates, 1);
+	assert!(!steps[0].fallback);
+
+	assert_eq!(steps[1].context, ["cat", "ate"]);
+	assert_eq!(steps[2].context, ["ate", "the"]);
+	assert_eq!(steps[2].probability, 0.5);
+	assert_eq!(steps[2].candidates, 2);
+}
+
+#[test]
+fn fallback() {
+	let mut chain = MarkovChain::new(2);
+	chain.add_tokens(TEXT.split(' '));
+
+	let mut rng = rand::rngs::StdRng::seed_from_u64(1337);
+	let step = chain.iter_traced(1, &mut rng).next().unwrap();
+	assert!(step.fallback);
+	assert!(step.context.is_empty());
+	assert!(step.probability > 0.0 && step.probability <= 1.0);
+
+	let step = chain
+		.iter_start_traced(["unknown"], 1, &mut rng)
+		.next()
+		.unwrap();
+	assert!(step.fallback);
+}